@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use libp2p::{autonat, gossipsub, identify, kad, mdns, ping, request_response, swarm::NetworkBehaviour, StreamProtocol};
+
+use crate::node::gossip_message_id;
+
+/// Combined set of libp2p protocols the node runs.
+///
+/// New protocols get a new field here and a new arm in [`BehaviourEvent`] handling in
+/// `node.rs`; nothing outside this module needs to know how the swarm is wired.
+#[derive(NetworkBehaviour)]
+pub struct Behaviour {
+    pub gossipsub: gossipsub::Behaviour,
+    pub kad: kad::Behaviour<kad::store::MemoryStore>,
+    pub identify: identify::Behaviour,
+    pub ping: ping::Behaviour,
+    pub mdns: mdns::tokio::Behaviour,
+    pub rr: request_response::cbor::Behaviour<Vec<u8>, Vec<u8>>,
+    pub autonat: autonat::Behaviour,
+}
+
+pub const RR_PROTOCOL: &str = "/stellaris/rr/1.0.0";
+pub const IDENTIFY_PROTOCOL_VERSION: &str = "/stellaris/1.0.0";
+pub const DEFAULT_KAD_PROTOCOL: &str = "/stellaris/kad/1.0.0";
+
+/// Knobs controlling how aggressively gossipsub rescues messages outside the mesh via
+/// IHAVE/IWANT gossip, distinct from mesh membership itself. `None` leaves gossipsub's own
+/// default for that parameter untouched.
+///
+/// Note: this version of `libp2p-gossipsub` doesn't expose IHAVE/IWANT as public
+/// `gossipsub::Event` variants (they're handled entirely inside the behaviour's internal
+/// heartbeat), so there is no way to surface sent/received counters for them from here
+/// without forking the dependency; only the emission parameters themselves are tunable.
+#[derive(Default, Clone, Copy)]
+pub struct GossipTuning {
+    pub gossip_lazy: Option<usize>,
+    pub gossip_factor: Option<f64>,
+    pub history_length: Option<usize>,
+    pub history_gossip: Option<usize>,
+}
+
+/// Knobs controlling Kademlia query behaviour and record lifetimes. `None` leaves
+/// `kad::Config`'s own default for that parameter untouched.
+#[derive(Default, Clone, Copy)]
+pub struct KadTuning {
+    pub replication_factor: Option<std::num::NonZeroUsize>,
+    pub query_timeout: Option<Duration>,
+    /// Alpha: how many peers a query contacts concurrently per round.
+    pub parallelism: Option<std::num::NonZeroUsize>,
+    /// `Some(None)` disables record expiry; `None` here leaves the library default.
+    pub record_ttl: Option<Option<Duration>>,
+    pub provider_record_ttl: Option<Option<Duration>>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn build(
+    keypair: &libp2p::identity::Keypair,
+    kad_protocol_name: &str,
+    identify_protocol_version: &str,
+    ping_interval: Duration,
+    max_transmit_size: usize,
+    flood_publish: bool,
+    gossip_tuning: GossipTuning,
+    kad_tuning: KadTuning,
+    dht_store_config: kad::store::MemoryStoreConfig,
+    autonat_use_connected: bool,
+    rr_request_timeout: Duration,
+) -> Result<Behaviour, String> {
+    let local_peer_id = libp2p::PeerId::from(keypair.public());
+
+    let mut gossipsub_config_builder = gossipsub::ConfigBuilder::default();
+    gossipsub_config_builder
+        .max_transmit_size(max_transmit_size)
+        .flood_publish(flood_publish)
+        .message_id_fn(gossip_message_id)
+        // Always on so `Node.set_replay_window` can reject replayed messages via
+        // `report_message_validation_result`; with no replay window configured, `node.rs`
+        // reports every message `Accept` as soon as it arrives, so this is a no-op for
+        // consumers that never touch replay protection.
+        .validate_messages();
+    if let Some(gossip_lazy) = gossip_tuning.gossip_lazy {
+        gossipsub_config_builder.gossip_lazy(gossip_lazy);
+    }
+    if let Some(gossip_factor) = gossip_tuning.gossip_factor {
+        gossipsub_config_builder.gossip_factor(gossip_factor);
+    }
+    if let Some(history_length) = gossip_tuning.history_length {
+        gossipsub_config_builder.history_length(history_length);
+    }
+    if let Some(history_gossip) = gossip_tuning.history_gossip {
+        gossipsub_config_builder.history_gossip(history_gossip);
+    }
+    let gossipsub_config = gossipsub_config_builder.build().map_err(|e| e.to_string())?;
+    let gossipsub = gossipsub::Behaviour::new(
+        gossipsub::MessageAuthenticity::Signed(keypair.clone()),
+        gossipsub_config,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let kad_protocol = StreamProtocol::try_from_owned(kad_protocol_name.to_string())
+        .map_err(|e| e.to_string())?;
+    let mut kad_config = kad::Config::new(kad_protocol);
+    // Inbound records are filtered through `InboundRequest::PutRecord`/`AddProvider` instead
+    // of being stored automatically, so `Node.set_record_validator` can reject them.
+    kad_config.set_record_filtering(kad::StoreInserts::FilterBoth);
+    if let Some(replication_factor) = kad_tuning.replication_factor {
+        kad_config.set_replication_factor(replication_factor);
+    }
+    if let Some(query_timeout) = kad_tuning.query_timeout {
+        kad_config.set_query_timeout(query_timeout);
+    }
+    if let Some(parallelism) = kad_tuning.parallelism {
+        kad_config.set_parallelism(parallelism);
+    }
+    if let Some(record_ttl) = kad_tuning.record_ttl {
+        kad_config.set_record_ttl(record_ttl);
+    }
+    if let Some(provider_record_ttl) = kad_tuning.provider_record_ttl {
+        kad_config.set_provider_record_ttl(provider_record_ttl);
+    }
+    let store = kad::store::MemoryStore::with_config(local_peer_id, dht_store_config);
+    let kad = kad::Behaviour::with_config(local_peer_id, store, kad_config);
+
+    // `hide_listen_addrs` is always on: `identify::Behaviour` otherwise advertises every listen
+    // address the swarm reports, with no hook for `Node.set_address_filter` to veto individual
+    // ones. Instead, `node.rs`'s `run_swarm` explicitly confirms each listen address that
+    // passes the current filter as an external address (`Swarm::add_external_address`), and
+    // that confirmed set is what actually gets sent — see the `NewListenAddr`/`SetAddressFilter`
+    // handling there.
+    let identify = identify::Behaviour::new(
+        identify::Config::new_with_signed_peer_record(identify_protocol_version.to_string(), keypair)
+            .with_hide_listen_addrs(true),
+    );
+
+    let ping = ping::Behaviour::new(ping::Config::default().with_interval(ping_interval));
+
+    let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)
+        .map_err(|e| e.to_string())?;
+
+    let rr = request_response::cbor::Behaviour::new(
+        [(StreamProtocol::new(RR_PROTOCOL), request_response::ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(rr_request_timeout),
+    );
+
+    // AutoNAT's dial-back protocol is inherently symmetric in this version: any node running
+    // it can both probe others and be probed by them, there's no way to disable responding to
+    // inbound probes. `autonat_use_connected` is the closest real client/server lever exposed
+    // by the config: whether this node opportunistically uses every connected peer as a
+    // dial-back candidate ("also acting as a server that probes others") versus restricting
+    // itself to explicitly-added servers only ("client-only").
+    let autonat_config = autonat::Config { use_connected: autonat_use_connected, ..Default::default() };
+    let autonat = autonat::Behaviour::new(local_peer_id, autonat_config);
+
+    Ok(Behaviour { gossipsub, kad, identify, ping, mdns, rr, autonat })
+}
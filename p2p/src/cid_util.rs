@@ -0,0 +1,70 @@
+use cid::multibase::Base;
+use cid::multihash::Multihash;
+use cid::Cid;
+use pyo3::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::error::P2pError;
+
+/// The SHA2-256 multicodec code, per the multicodec table.
+const SHA2_256: u64 = 0x12;
+
+/// Content-identifier helpers for addressing DHT records the same way IPFS does, so
+/// records stored under a CID key can be handed directly to IPFS-compatible tooling.
+///
+/// Stateless by design: every method is a `#[staticmethod]`, there's nothing to
+/// construct an instance around.
+#[pyclass]
+pub struct ContentId;
+
+#[pymethods]
+impl ContentId {
+    /// Hashes `data` with SHA-256, wraps the digest in a multihash, and builds a CIDv1
+    /// using `codec` (e.g. `0x55` for raw, `0x70` for dag-pb), returned base32-lower
+    /// encoded as used by IPFS's default CID string representation.
+    #[staticmethod]
+    fn to_cidv1(data: Vec<u8>, codec: u64) -> PyResult<String> {
+        let digest = Sha256::digest(&data);
+        let hash = Multihash::<64>::wrap(SHA2_256, &digest)
+            .map_err(|e| P2pError::Other(format!("failed to wrap digest in multihash: {e}")))?;
+        let cid = Cid::new_v1(codec, hash);
+        cid.to_string_of_base(Base::Base32Lower)
+            .map_err(|e| P2pError::Other(format!("failed to encode CID: {e}")).into())
+    }
+
+    /// Parses a CID string back into its multicodec code and raw multihash bytes.
+    #[staticmethod]
+    fn parse_cid(cid_str: &str) -> PyResult<(u64, Vec<u8>)> {
+        let cid: Cid = cid_str.parse().map_err(|e| P2pError::Other(format!("invalid CID {cid_str}: {e}")))?;
+        Ok((cid.codec(), cid.hash().to_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod cid_tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_codec_and_digest_through_a_cid_string() {
+        let data = b"hello DHT".to_vec();
+        let cid_str = ContentId::to_cidv1(data.clone(), 0x55).unwrap();
+
+        let (codec, hash_bytes) = ContentId::parse_cid(&cid_str).unwrap();
+        assert_eq!(codec, 0x55);
+
+        let digest = Sha256::digest(&data);
+        let expected_hash = Multihash::<64>::wrap(SHA2_256, &digest).unwrap();
+        assert_eq!(hash_bytes, expected_hash.to_bytes());
+    }
+
+    #[test]
+    fn same_data_and_codec_produce_the_same_cid() {
+        let data = b"deterministic".to_vec();
+        assert_eq!(ContentId::to_cidv1(data.clone(), 0x70).unwrap(), ContentId::to_cidv1(data, 0x70).unwrap());
+    }
+
+    #[test]
+    fn parse_cid_rejects_garbage() {
+        assert!(ContentId::parse_cid("not a cid").is_err());
+    }
+}
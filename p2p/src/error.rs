@@ -0,0 +1,25 @@
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::PyErr;
+use thiserror::Error;
+
+/// Errors surfaced by the networking core before they cross the PyO3 boundary.
+///
+/// Every variant maps to `PyRuntimeError` on the Python side; callers that need to
+/// branch on the failure kind should match on the error message for now.
+#[derive(Debug, Error)]
+pub enum P2pError {
+    #[error("transport error: {0}")]
+    Transport(String),
+    #[error("invalid multiaddr: {0}")]
+    InvalidMultiaddr(String),
+    #[error("invalid keypair: {0}")]
+    InvalidKeypair(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<P2pError> for PyErr {
+    fn from(err: P2pError) -> PyErr {
+        PyRuntimeError::new_err(err.to_string())
+    }
+}
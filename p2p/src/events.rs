@@ -0,0 +1,282 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A single swarm event, translated into something Python can consume.
+///
+/// Events are built on the swarm's driver thread (see `node.rs`) and only converted to
+/// Python objects lazily, when `Node.poll_event`/`Node.get_events` is called, so the
+/// driver thread never needs to take the GIL.
+#[derive(Debug, Clone)]
+pub enum NodeEvent {
+    ConnectionEstablished {
+        peer_id: String,
+        num_established: u32,
+        /// `"outbound"` if this node dialed the peer, `"inbound"` if the peer dialed us.
+        direction: &'static str,
+    },
+    ConnectionClosed {
+        peer_id: String,
+        /// Short reason code, e.g. "KeepAliveTimeout", "Disconnected", or the error text.
+        reason: String,
+        remaining_connections: u32,
+    },
+    ReconnectScheduled {
+        peer_id: String,
+        attempt: u32,
+        backoff_secs: u64,
+    },
+    OutgoingConnectionError {
+        peer_id: Option<String>,
+        error: String,
+    },
+    ListenerClosed {
+        listener_id: String,
+        addresses: Vec<String>,
+        reason: Option<String>,
+    },
+    ListenerError {
+        listener_id: String,
+        error: String,
+    },
+    IdentifyReceived {
+        peer_id: String,
+        listen_addrs: Vec<String>,
+        protocols: Vec<String>,
+        /// Whether the identify response carried a verifiable signed peer record.
+        certified: bool,
+    },
+    RateLimited {
+        /// The remote address the dropped inbound connection attempt came from.
+        address: String,
+    },
+    FileTransferProgress {
+        transfer_id: u64,
+        peer_id: String,
+        protocol: String,
+        seq: u32,
+        total: u32,
+        direction: String,
+        complete: bool,
+        /// The full reassembled file, set only on the receiving end's final chunk.
+        data: Option<Vec<u8>>,
+    },
+    RoutingUpdated {
+        peer_id: String,
+        /// Whether this peer was newly inserted into a k-bucket, as opposed to an existing
+        /// entry simply gaining another known address.
+        is_new_peer: bool,
+    },
+    Heartbeat {
+        /// Number of distinct peers currently connected.
+        peer_count: usize,
+        uptime_secs: f64,
+    },
+    AutonatProbe {
+        /// `"inbound"` if a remote asked this node to dial it back, `"outbound"` if this node
+        /// asked a remote to dial it back.
+        direction: &'static str,
+        peer_id: Option<String>,
+        /// The address dialed (on success) or, for an inbound request, the first address that
+        /// will be attempted.
+        address: Option<String>,
+        /// `"requested"`, `"succeeded"`, or `"failed"`.
+        outcome: &'static str,
+        error: Option<String>,
+    },
+    GossipMessage {
+        topic: String,
+        data: Vec<u8>,
+        source: Option<String>,
+        /// Protobuf-encoded public key of the application-level signer, if the publisher set
+        /// one via `set_gossip_signing_key`.
+        signer_pubkey: Option<Vec<u8>>,
+        /// Whether `signer_pubkey`'s signature over `data` checked out. `None` if the message
+        /// carried no application-level signature at all.
+        signer_verified: Option<bool>,
+    },
+    OutboundFailure {
+        transfer_id: u64,
+        peer_id: String,
+        /// The request-response failure's display text, e.g. "Timeout while waiting for a response".
+        error: String,
+        /// Number of retries already attempted for this chunk before giving up.
+        retries: u32,
+    },
+    UnauthenticatedRequest {
+        peer_id: String,
+        /// Why the request was rejected, e.g. "missing signature" or "invalid signature".
+        reason: String,
+    },
+    /// A connection to `peer_id` was torn down because it (or this node) tried to open a
+    /// stream past `Node.new`'s `yamux_max_num_streams` limit.
+    StreamLimitReached {
+        peer_id: String,
+    },
+    /// A `Node.put_record` query has finished (or timed out), reporting whether it reached the
+    /// requested quorum and how many nodes actually confirmed storing the record.
+    PutRecordResult {
+        key: Vec<u8>,
+        success: bool,
+        num_nodes: u32,
+        error: Option<String>,
+    },
+    /// A peer was disconnected by `Node.set_min_agent_version` because its identify
+    /// `agent_version` didn't satisfy the configured requirement.
+    IncompatiblePeer {
+        peer_id: String,
+        agent_version: String,
+    },
+    /// One of this node's own external addresses was confirmed reachable, e.g. via identify's
+    /// address observation or an explicit `Node.add_external_address` call.
+    ExternalAddrConfirmed {
+        address: String,
+    },
+    /// A previously confirmed external address of this node is no longer considered reachable.
+    ExternalAddrExpired {
+        address: String,
+    },
+    /// A peer sent a request via `Node.request` that isn't a file-transfer chunk and isn't an
+    /// empty keep-alive ping (see `Node.keep_alive_peer`), both of which are handled internally
+    /// without ever reaching Python. Call `Node.respond(request_id, ...)` to answer it.
+    IncomingRequest {
+        request_id: u64,
+        peer_id: String,
+        /// The caller-chosen logical protocol name passed to the sender's `Node.request` call.
+        protocol: String,
+        data: Vec<u8>,
+    },
+}
+
+impl NodeEvent {
+    pub fn into_py(self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let dict = PyDict::new(py);
+        match self {
+            NodeEvent::ConnectionEstablished { peer_id, num_established, direction } => {
+                dict.set_item("type", "ConnectionEstablished")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("num_established", num_established)?;
+                dict.set_item("data", direction)?;
+            }
+            NodeEvent::ConnectionClosed { peer_id, reason, remaining_connections } => {
+                dict.set_item("type", "ConnectionClosed")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("data", reason)?;
+                dict.set_item("remaining_connections", remaining_connections)?;
+            }
+            NodeEvent::ReconnectScheduled { peer_id, attempt, backoff_secs } => {
+                dict.set_item("type", "ReconnectScheduled")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("attempt", attempt)?;
+                dict.set_item("backoff_secs", backoff_secs)?;
+            }
+            NodeEvent::OutgoingConnectionError { peer_id, error } => {
+                dict.set_item("type", "OutgoingConnectionError")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("error", error)?;
+            }
+            NodeEvent::ListenerClosed { listener_id, addresses, reason } => {
+                dict.set_item("type", "ListenerClosed")?;
+                dict.set_item("listener_id", listener_id)?;
+                dict.set_item("addresses", addresses)?;
+                dict.set_item("reason", reason)?;
+            }
+            NodeEvent::ListenerError { listener_id, error } => {
+                dict.set_item("type", "ListenerError")?;
+                dict.set_item("listener_id", listener_id)?;
+                dict.set_item("error", error)?;
+            }
+            NodeEvent::IdentifyReceived { peer_id, listen_addrs, protocols, certified } => {
+                dict.set_item("type", "IdentifyReceived")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("listen_addrs", listen_addrs)?;
+                dict.set_item("protocols", protocols)?;
+                dict.set_item("certified", certified)?;
+            }
+            NodeEvent::RateLimited { address } => {
+                dict.set_item("type", "RateLimited")?;
+                dict.set_item("address", address)?;
+            }
+            NodeEvent::FileTransferProgress { transfer_id, peer_id, protocol, seq, total, direction, complete, data } => {
+                dict.set_item("type", "FileTransferProgress")?;
+                dict.set_item("transfer_id", transfer_id)?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("protocol", protocol)?;
+                dict.set_item("seq", seq)?;
+                dict.set_item("total", total)?;
+                dict.set_item("direction", direction)?;
+                dict.set_item("complete", complete)?;
+                dict.set_item("data", data)?;
+            }
+            NodeEvent::RoutingUpdated { peer_id, is_new_peer } => {
+                dict.set_item("type", "RoutingUpdated")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("is_new_peer", is_new_peer)?;
+            }
+            NodeEvent::Heartbeat { peer_count, uptime_secs } => {
+                dict.set_item("type", "Heartbeat")?;
+                dict.set_item("peer_count", peer_count)?;
+                dict.set_item("uptime_secs", uptime_secs)?;
+            }
+            NodeEvent::AutonatProbe { direction, peer_id, address, outcome, error } => {
+                dict.set_item("type", "AutonatProbe")?;
+                dict.set_item("direction", direction)?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("address", address)?;
+                dict.set_item("outcome", outcome)?;
+                dict.set_item("error", error)?;
+            }
+            NodeEvent::GossipMessage { topic, data, source, signer_pubkey, signer_verified } => {
+                dict.set_item("type", "GossipMessage")?;
+                dict.set_item("topic", topic)?;
+                dict.set_item("data", data)?;
+                dict.set_item("source", source)?;
+                dict.set_item("signer_pubkey", signer_pubkey)?;
+                dict.set_item("signer_verified", signer_verified)?;
+            }
+            NodeEvent::OutboundFailure { transfer_id, peer_id, error, retries } => {
+                dict.set_item("type", "OutboundFailure")?;
+                dict.set_item("transfer_id", transfer_id)?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("error", error)?;
+                dict.set_item("retries", retries)?;
+            }
+            NodeEvent::UnauthenticatedRequest { peer_id, reason } => {
+                dict.set_item("type", "UnauthenticatedRequest")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("reason", reason)?;
+            }
+            NodeEvent::StreamLimitReached { peer_id } => {
+                dict.set_item("type", "StreamLimitReached")?;
+                dict.set_item("peer_id", peer_id)?;
+            }
+            NodeEvent::PutRecordResult { key, success, num_nodes, error } => {
+                dict.set_item("type", "PutRecordResult")?;
+                dict.set_item("key", key)?;
+                dict.set_item("success", success)?;
+                dict.set_item("num_nodes", num_nodes)?;
+                dict.set_item("error", error)?;
+            }
+            NodeEvent::IncompatiblePeer { peer_id, agent_version } => {
+                dict.set_item("type", "IncompatiblePeer")?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("data", agent_version)?;
+            }
+            NodeEvent::ExternalAddrConfirmed { address } => {
+                dict.set_item("type", "ExternalAddrConfirmed")?;
+                dict.set_item("data", address)?;
+            }
+            NodeEvent::ExternalAddrExpired { address } => {
+                dict.set_item("type", "ExternalAddrExpired")?;
+                dict.set_item("data", address)?;
+            }
+            NodeEvent::IncomingRequest { request_id, peer_id, protocol, data } => {
+                dict.set_item("type", "IncomingRequest")?;
+                dict.set_item("request_id", request_id)?;
+                dict.set_item("peer_id", peer_id)?;
+                dict.set_item("protocol", protocol)?;
+                dict.set_item("data", data)?;
+            }
+        }
+        Ok(dict.into())
+    }
+}
@@ -0,0 +1,34 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// BLAKE3 hashing helpers, for key derivation and content commitments that don't need
+/// CID wrapping (see `ContentId` for that).
+///
+/// Stateless by design: every method is a `#[staticmethod]`, there's nothing to
+/// construct an instance around.
+#[pyclass]
+pub struct HashManager;
+
+#[pymethods]
+impl HashManager {
+    /// Hashes `data` with BLAKE3, returning the standard 32-byte digest.
+    #[staticmethod]
+    fn blake3_hash(data: Vec<u8>) -> Vec<u8> {
+        blake3::hash(&data).as_bytes().to_vec()
+    }
+
+    /// Hashes `data` with BLAKE3 in extendable-output (XOF) mode, producing `out_len` bytes
+    /// instead of the fixed 32-byte digest. Useful for deriving multiple subkeys from one
+    /// secret without pulling in a separate KDF dependency.
+    #[staticmethod]
+    fn blake3_xof(data: Vec<u8>, out_len: usize) -> PyResult<Vec<u8>> {
+        if out_len == 0 {
+            return Err(PyValueError::new_err("out_len must be greater than 0"));
+        }
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&data);
+        let mut output = vec![0u8; out_len];
+        hasher.finalize_xof().fill(&mut output);
+        Ok(output)
+    }
+}
@@ -0,0 +1,31 @@
+use pyo3::prelude::*;
+
+mod behaviour;
+mod cid_util;
+mod error;
+mod events;
+mod hash_util;
+mod keypair;
+mod multiaddr;
+mod node;
+mod storage;
+mod transfer;
+
+use cid_util::ContentId;
+use hash_util::HashManager;
+use keypair::KeypairManager;
+use multiaddr::MultiaddrBuilder;
+use node::{NetworkInfo, Node};
+use storage::PersistentStorage;
+
+#[pymodule]
+fn stellaris_p2p(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Node>()?;
+    m.add_class::<NetworkInfo>()?;
+    m.add_class::<KeypairManager>()?;
+    m.add_class::<PersistentStorage>()?;
+    m.add_class::<MultiaddrBuilder>()?;
+    m.add_class::<ContentId>()?;
+    m.add_class::<HashManager>()?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use pyo3::prelude::*;
+
+use crate::error::P2pError;
+
+/// Fluent builder for multiaddrs, so callers don't have to hand-concatenate address
+/// segments. Most useful for circuit-relay addresses, which chain a relay hop's `p2p()`
+/// peer id, a `p2p_circuit()` marker, and the relayed target's `p2p()` peer id in sequence.
+#[pyclass]
+pub struct MultiaddrBuilder {
+    addr: Multiaddr,
+}
+
+#[pymethods]
+impl MultiaddrBuilder {
+    #[new]
+    fn new() -> Self {
+        Self { addr: Multiaddr::empty() }
+    }
+
+    fn ip4(&mut self, addr: &str) -> PyResult<()> {
+        let ip = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{addr}: {e}")))?;
+        self.addr.push(Protocol::Ip4(ip));
+        Ok(())
+    }
+
+    fn ip6(&mut self, addr: &str) -> PyResult<()> {
+        let ip = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{addr}: {e}")))?;
+        self.addr.push(Protocol::Ip6(ip));
+        Ok(())
+    }
+
+    fn dns(&mut self, name: String) {
+        self.addr.push(Protocol::Dns(name.into()));
+    }
+
+    fn tcp(&mut self, port: u16) {
+        self.addr.push(Protocol::Tcp(port));
+    }
+
+    fn udp(&mut self, port: u16) {
+        self.addr.push(Protocol::Udp(port));
+    }
+
+    fn quic_v1(&mut self) {
+        self.addr.push(Protocol::QuicV1);
+    }
+
+    fn ws(&mut self) {
+        self.addr.push(Protocol::Ws("".into()));
+    }
+
+    /// Appends `/wss`, for a TLS-terminated websocket, e.g. `/dns4/.../tcp/443/wss`. See
+    /// `Node.new`'s `wss_cert_der`/`wss_key_der` for configuring what certificate the node
+    /// presents to `wss` clients.
+    fn wss(&mut self) {
+        self.addr.push(Protocol::Wss("/".into()));
+    }
+
+    /// Appends a `/p2p/<peer_id>` segment. Can be used more than once in the same
+    /// address: once for a relay hop's peer id before `p2p_circuit()`, and again for the
+    /// relayed target's peer id after it.
+    fn p2p(&mut self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId =
+            peer_id.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("invalid peer id {peer_id}: {e}")))?;
+        self.addr.push(Protocol::P2p(peer_id));
+        Ok(())
+    }
+
+    /// Appends `/p2p-circuit`, marking every segment appended after it as reached by
+    /// relaying through whatever was dialed so far, e.g.
+    /// `/ip4/.../tcp/.../p2p/<relay>/p2p-circuit/p2p/<target>`.
+    fn p2p_circuit(&mut self) {
+        self.addr.push(Protocol::P2pCircuit);
+    }
+
+    /// The multiaddr built so far, as a string suitable for `Node.dial`/`Node.listen`.
+    fn build(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Parses `addr` and re-renders it through `Multiaddr`'s own `Display`, collapsing
+    /// textual variations (e.g. a leading zero in a port) that don't change the decoded
+    /// address. Useful before deduping addresses gathered from mdns, kad, and identify,
+    /// which rarely agree on formatting even when they mean the same address.
+    #[staticmethod]
+    fn normalize(addr: &str) -> PyResult<String> {
+        let parsed: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{addr}: {e}")))?;
+        Ok(parsed.to_string())
+    }
+
+    /// Whether `a` and `b` parse to the same `Multiaddr`, rather than comparing the raw
+    /// strings.
+    #[staticmethod]
+    fn addresses_equal(a: &str, b: &str) -> PyResult<bool> {
+        let a: Multiaddr = a.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{a}: {e}")))?;
+        let b: Multiaddr = b.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{b}: {e}")))?;
+        Ok(a == b)
+    }
+}
+
+#[cfg(test)]
+mod normalization_tests {
+    use super::*;
+
+    #[test]
+    fn normalize_collapses_a_leading_zero_in_the_port() {
+        assert_eq!(MultiaddrBuilder::normalize("/ip4/127.0.0.1/tcp/08080").unwrap(), "/ip4/127.0.0.1/tcp/8080");
+    }
+
+    #[test]
+    fn normalize_rejects_garbage() {
+        assert!(MultiaddrBuilder::normalize("not a multiaddr").is_err());
+    }
+
+    #[test]
+    fn addresses_equal_ignores_textual_differences() {
+        assert!(MultiaddrBuilder::addresses_equal("/ip4/127.0.0.1/tcp/08080", "/ip4/127.0.0.1/tcp/8080").unwrap());
+    }
+
+    #[test]
+    fn addresses_equal_is_false_for_different_addresses() {
+        assert!(!MultiaddrBuilder::addresses_equal("/ip4/127.0.0.1/tcp/8080", "/ip4/127.0.0.1/tcp/8081").unwrap());
+    }
+}
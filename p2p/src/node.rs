@@ -0,0 +1,4644 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use futures::StreamExt;
+use libp2p::autonat;
+use libp2p::gossipsub;
+use libp2p::identify;
+use libp2p::multiaddr::Protocol;
+use libp2p::ping;
+use libp2p::swarm::{ConnectionError, ConnectionId, SwarmEvent};
+use libp2p::kad::store::RecordStore;
+use libp2p::request_response;
+use libp2p::{kad, Multiaddr, PeerId, Swarm, Transport};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+use tokio::sync::mpsc;
+
+use crate::behaviour::{self, Behaviour, BehaviourEvent};
+use crate::error::P2pError;
+use crate::events::NodeEvent;
+use crate::keypair::KeypairManager;
+use crate::transfer::{self, FileChunk};
+
+/// Initial re-dial delay for a persistent peer; doubles on each failed attempt.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound on the re-dial backoff, so a long-dead peer is still retried occasionally.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+struct PersistentPeer {
+    addr: Multiaddr,
+    attempt: u32,
+}
+
+/// Caps how many events `peek_events`/`events_since` can see at once, independent of how far
+/// behind a cursor-based consumer has fallen. Past this, the oldest entries are dropped from
+/// the log (but not from the drain queue `poll_event`/`get_events` consume).
+const EVENT_LOG_CAPACITY: usize = 10_000;
+
+/// Default cap on `Node`'s recent-gossip-message cache; see `set_gossip_cache_capacity`.
+const DEFAULT_GOSSIP_CACHE_CAPACITY: usize = 1000;
+
+/// Backs `Node.events`. Every pushed event goes into both a FIFO `queue`, which
+/// `poll_event`/`get_events`/`poll_once` drain destructively exactly as before, and a
+/// separately-retained, sequence-numbered `log`, which `peek_events`/`events_since` read
+/// without taking anything away from `queue`'s consumer. This is what lets a draining
+/// consumer and a peeking/cursor-based one coexist without the latter stealing events meant
+/// for the former.
+#[derive(Default)]
+struct EventQueue {
+    queue: VecDeque<NodeEvent>,
+    log: VecDeque<(u64, NodeEvent)>,
+    next_seq: u64,
+}
+
+impl EventQueue {
+    fn push_back(&mut self, event: NodeEvent) {
+        self.queue.push_back(event.clone());
+        // Sequence numbers start at 1, so a cursor of 0 precedes every logged event.
+        self.next_seq += 1;
+        self.log.push_back((self.next_seq, event));
+        if self.log.len() > EVENT_LOG_CAPACITY {
+            self.log.pop_front();
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    fn pop_front(&mut self) -> Option<NodeEvent> {
+        self.queue.pop_front()
+    }
+
+    fn drain_all(&mut self) -> Vec<NodeEvent> {
+        self.queue.drain(..).collect()
+    }
+
+    /// All events currently retained in the log, oldest first, without removing them.
+    fn peek_all(&self) -> Vec<NodeEvent> {
+        self.log.iter().map(|(_, event)| event.clone()).collect()
+    }
+
+    /// Events logged after `cursor` (exclusive), plus the cursor a follow-up call should pass
+    /// to pick up from there. If the log has trimmed past `cursor` (the consumer fell behind
+    /// `EVENT_LOG_CAPACITY` events), this returns everything still retained rather than
+    /// silently skipping the gap.
+    fn since(&self, cursor: u64) -> (Vec<NodeEvent>, u64) {
+        let events: Vec<NodeEvent> =
+            self.log.iter().filter(|(seq, _)| *seq > cursor).map(|(_, event)| event.clone()).collect();
+        let new_cursor = self.log.back().map(|(seq, _)| *seq).unwrap_or(cursor);
+        (events, new_cursor)
+    }
+}
+
+/// Snapshot of a single open connection, refreshed as `ConnectionEstablished`/
+/// `ConnectionClosed` swarm events arrive. Read by `Node::get_connection_details`.
+struct ConnectionRecord {
+    peer_id: PeerId,
+    remote_addr: String,
+    direction: &'static str,
+    opened_at: Instant,
+    /// Whether the remote address goes through a `/p2p-circuit` relay hop rather than
+    /// reaching the peer directly. DCUtR upgrades a relayed connection to direct by opening
+    /// a fresh one and closing the relayed one, so watching this flip to `false` for the
+    /// same peer is how hole-punch success shows up here.
+    relayed: bool,
+}
+
+/// Running per-peer stats fed into `Node.get_peer_health`, updated as ping and
+/// connection-close events arrive.
+#[derive(Default)]
+struct PeerStats {
+    last_ping_rtt: Option<Duration>,
+    disconnect_count: u32,
+}
+
+/// Per-IP token bucket used by `set_inbound_rate_limit` to drop inbound connection floods.
+struct InboundRateLimiter {
+    per_ip_per_sec: f64,
+    burst: f64,
+    buckets: HashMap<std::net::IpAddr, (f64, Instant)>,
+}
+
+impl InboundRateLimiter {
+    fn new(per_ip_per_sec: f64, burst: f64) -> Self {
+        Self { per_ip_per_sec, burst, buckets: HashMap::new() }
+    }
+
+    /// Refills `ip`'s bucket for the elapsed time and consumes a token if one is available.
+    fn allow(&mut self, ip: std::net::IpAddr) -> bool {
+        let now = Instant::now();
+        let (tokens, last) = self.buckets.entry(ip).or_insert((self.burst, now));
+        let elapsed = now.duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.per_ip_per_sec).min(self.burst);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Which topic-hashing scheme to use for gossipsub topics.
+///
+/// `Ident` uses the topic name itself as the hash, which is what this crate has always done.
+/// `Sha256` hashes the topic name, which some existing gossipsub networks expect; joining one
+/// of those requires matching its scheme exactly, since peers with mismatched schemes compute
+/// different `TopicHash`es for the same topic name and never end up in each other's mesh.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TopicSchemeKind {
+    Ident,
+    Sha256,
+}
+
+/// Which topic-hashing scheme to use for gossipsub topics, plus an optional namespace
+/// prefix so unrelated networks sharing a process never collide on the same topic hash.
+struct TopicScheme {
+    kind: TopicSchemeKind,
+    /// Set from `Node.new`'s `network_name`; prepended as `"<namespace>/<topic>"` to every
+    /// topic name before hashing/subscribing. `None` reproduces the pre-existing behavior of
+    /// using the topic name exactly as given.
+    namespace: Option<String>,
+}
+
+impl TopicScheme {
+    fn parse(name: &str, namespace: Option<String>) -> PyResult<Self> {
+        let kind = match name {
+            "ident" => TopicSchemeKind::Ident,
+            "sha256" => TopicSchemeKind::Sha256,
+            other => return Err(P2pError::Other(format!("unknown gossipsub topic scheme: {other}")).into()),
+        };
+        Ok(TopicScheme { kind, namespace })
+    }
+
+    fn namespaced(&self, topic_name: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{namespace}/{topic_name}"),
+            None => topic_name.to_string(),
+        }
+    }
+
+    fn hash(&self, topic_name: &str) -> gossipsub::TopicHash {
+        let topic_name = self.namespaced(topic_name);
+        match self.kind {
+            TopicSchemeKind::Ident => gossipsub::IdentTopic::new(topic_name).hash(),
+            TopicSchemeKind::Sha256 => gossipsub::Sha256Topic::new(topic_name).hash(),
+        }
+    }
+
+    fn subscribe(&self, behaviour: &mut Behaviour, topic_name: &str) -> Result<bool, gossipsub::SubscriptionError> {
+        let topic_name = self.namespaced(topic_name);
+        match self.kind {
+            TopicSchemeKind::Ident => behaviour.gossipsub.subscribe(&gossipsub::IdentTopic::new(topic_name)),
+            TopicSchemeKind::Sha256 => behaviour.gossipsub.subscribe(&gossipsub::Sha256Topic::new(topic_name)),
+        }
+    }
+
+    fn unsubscribe(&self, behaviour: &mut Behaviour, topic_name: &str) -> bool {
+        let topic_name = self.namespaced(topic_name);
+        match self.kind {
+            TopicSchemeKind::Ident => behaviour.gossipsub.unsubscribe(&gossipsub::IdentTopic::new(topic_name)),
+            TopicSchemeKind::Sha256 => behaviour.gossipsub.unsubscribe(&gossipsub::Sha256Topic::new(topic_name)),
+        }
+    }
+}
+
+/// Per-topic token bucket used by `set_publish_rate_limit` to throttle `publish_gossip`,
+/// protecting the local mesh and remote peers' scoring thresholds from a buggy publish loop.
+struct PublishRateLimit {
+    messages_per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+    /// If true, `publish_gossip` waits for a token instead of failing with `PublishRateLimited`.
+    block: bool,
+}
+
+impl PublishRateLimit {
+    fn new(messages_per_sec: f64, block: bool) -> Self {
+        Self { messages_per_sec, tokens: messages_per_sec.max(1.0), last_refill: Instant::now(), block }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.messages_per_sec).min(self.messages_per_sec.max(1.0));
+        self.last_refill = now;
+    }
+
+    fn try_consume(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How long to wait before a token will be available, given the current deficit.
+    fn wait_duration(&self) -> Duration {
+        Duration::from_secs_f64(((1.0 - self.tokens).max(0.0)) / self.messages_per_sec)
+    }
+}
+
+/// Authoritative per-topic gossip counters, maintained directly in the event loop as messages
+/// are actually published/received, rather than derived from whatever the app happened to
+/// cache. Backs `get_live_gossip_stats`.
+#[derive(Default, Clone)]
+struct TopicGossipStats {
+    published: u64,
+    received: u64,
+    /// Rejected because their replay-protection nonce had already been seen (see
+    /// `set_replay_window`). Always `0` while replay protection is disabled, since gossipsub's
+    /// own message cache deduplicates identical messages before they ever reach this crate.
+    duplicates_rejected: u64,
+    /// Rejected for any other reason a `set_replay_window` check can fail: a missing envelope
+    /// or a timestamp outside the configured window.
+    validation_failures: u64,
+}
+
+/// An outbound file transfer with one chunk in flight. The next chunk is only read off
+/// disk and sent once this one's ack comes back, so an unresponsive peer stalls the
+/// sender instead of it buffering the rest of the file in memory.
+struct OutboundTransfer {
+    transfer_id: u64,
+    protocol: String,
+    peer_id: PeerId,
+    file: std::fs::File,
+    seq: u32,
+    total: u32,
+    /// The exact bytes last sent for `seq`, kept around so a timed-out chunk can be resent
+    /// verbatim instead of re-reading (and potentially desyncing with) the file offset.
+    chunk_data: Vec<u8>,
+    /// Number of times the in-flight chunk (`seq`) has already been retried after a timeout.
+    retries_used: u32,
+}
+
+/// A `listen_blocking` call waiting on its listener's first `NewListenAddr`, or a timeout.
+struct PendingListen {
+    listener_id: libp2p::core::transport::ListenerId,
+    deadline: Instant,
+    reply: Option<tokio::sync::oneshot::Sender<Result<Vec<Multiaddr>, String>>>,
+}
+
+/// A `get_record` call waiting on values from an in-flight Kademlia query, or a timeout.
+struct PendingGetRecord {
+    quorum: usize,
+    values: Vec<Vec<u8>>,
+    deadline: Instant,
+    reply: Option<tokio::sync::oneshot::Sender<Result<Vec<Vec<u8>>, String>>>,
+}
+
+/// A `Node.request` call waiting on the matching `rr` response, or a timeout. Resolved from
+/// three places: the response arriving (`Message::Response`), the library giving up on the
+/// request first (`OutboundFailure`, e.g. the peer disconnecting), or `deadline` passing here.
+struct PendingRrRequest {
+    deadline: Instant,
+    reply: Option<tokio::sync::oneshot::Sender<Result<Vec<u8>, String>>>,
+}
+
+/// Wraps `data` for publishing on gossipsub. When `signing_key` is set, prepends a marker
+/// byte of `1` followed by the signer's protobuf-encoded public key and its signature over
+/// `data` (both length-prefixed), so receivers can verify the application-level publisher
+/// independently of the transport identity gossipsub itself signs with. Otherwise prepends a
+/// marker byte of `0` and leaves `data` untouched.
+fn encode_gossip_payload(signing_key: Option<&libp2p::identity::Keypair>, data: Vec<u8>) -> PyResult<Vec<u8>> {
+    let Some(signing_key) = signing_key else {
+        let mut out = Vec::with_capacity(1 + data.len());
+        out.push(0u8);
+        out.extend_from_slice(&data);
+        return Ok(out);
+    };
+    let pubkey = signing_key.public().encode_protobuf();
+    let signature = signing_key.sign(&data).map_err(|e| P2pError::Other(e.to_string()))?;
+    let mut out = Vec::with_capacity(1 + 4 + pubkey.len() + 4 + signature.len() + data.len());
+    out.push(1u8);
+    out.extend_from_slice(&(pubkey.len() as u32).to_le_bytes());
+    out.extend_from_slice(&pubkey);
+    out.extend_from_slice(&(signature.len() as u32).to_le_bytes());
+    out.extend_from_slice(&signature);
+    out.extend_from_slice(&data);
+    Ok(out)
+}
+
+/// Reverses [`encode_gossip_payload`]. Returns the inner data plus, if the message carried an
+/// application-level signature, the signer's public key and whether it checked out. Malformed
+/// envelopes are treated as unsigned raw data rather than dropped, since a peer running an
+/// older wire format may not send the marker byte at all.
+fn decode_gossip_payload(bytes: Vec<u8>) -> (Vec<u8>, Option<Vec<u8>>, Option<bool>) {
+    fn try_decode(bytes: &[u8]) -> Option<(Vec<u8>, Vec<u8>, bool)> {
+        let (&marker, rest) = bytes.split_first()?;
+        if marker != 1 {
+            return None;
+        }
+        let (pubkey_len, rest) = rest.split_at_checked(4)?;
+        let pubkey_len = u32::from_le_bytes(pubkey_len.try_into().unwrap()) as usize;
+        let (pubkey, rest) = rest.split_at_checked(pubkey_len)?;
+        let (sig_len, rest) = rest.split_at_checked(4)?;
+        let sig_len = u32::from_le_bytes(sig_len.try_into().unwrap()) as usize;
+        let (signature, data) = rest.split_at_checked(sig_len)?;
+        let public_key = libp2p::identity::PublicKey::try_decode_protobuf(pubkey).ok()?;
+        let verified = public_key.verify(data, signature);
+        Some((data.to_vec(), pubkey.to_vec(), verified))
+    }
+
+    match try_decode(&bytes) {
+        Some((data, pubkey, verified)) => (data, Some(pubkey), Some(verified)),
+        None => {
+            let data = match explicit_id_and_data(&bytes) {
+                Some((_, data)) => data.to_vec(),
+                None if bytes.first() == Some(&0) => bytes[1..].to_vec(),
+                None => bytes,
+            };
+            (data, None, None)
+        }
+    }
+}
+
+/// Marker byte identifying [`encode_gossip_payload_with_id`]'s envelope, distinct from
+/// [`encode_gossip_payload`]'s `0`/`1` markers.
+const EXPLICIT_ID_MARKER: u8 = 2;
+
+/// Marker byte identifying [`encode_replay_envelope`]'s envelope.
+const REPLAY_ENVELOPE_MARKER: u8 = 3;
+
+/// Wraps `data` with a unix timestamp and a random nonce, for `set_replay_window` to check on
+/// receipt. Applied as the `data` argument to [`encode_gossip_payload`], so when
+/// `set_gossip_signing_key` is also in use, the timestamp and nonce are covered by that
+/// signature too and can't be stripped or altered by a relaying peer.
+fn encode_replay_envelope(data: Vec<u8>) -> Vec<u8> {
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut nonce = [0u8; 16];
+    OsRng.fill_bytes(&mut nonce);
+    let mut out = Vec::with_capacity(1 + 8 + nonce.len() + data.len());
+    out.push(REPLAY_ENVELOPE_MARKER);
+    out.extend_from_slice(&timestamp.to_le_bytes());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&data);
+    out
+}
+
+/// Reverses [`encode_replay_envelope`]. `None` if `bytes` doesn't carry the envelope at all,
+/// e.g. a peer that isn't publishing with replay protection enabled.
+fn decode_replay_envelope(bytes: &[u8]) -> Option<(u64, [u8; 16], &[u8])> {
+    let (&marker, rest) = bytes.split_first()?;
+    if marker != REPLAY_ENVELOPE_MARKER {
+        return None;
+    }
+    let (timestamp, rest) = rest.split_at_checked(8)?;
+    let timestamp = u64::from_le_bytes(timestamp.try_into().unwrap());
+    let (nonce, data) = rest.split_at_checked(16)?;
+    Some((timestamp, nonce.try_into().unwrap(), data))
+}
+
+/// Decides whether an incoming gossip message (already stripped of its signature envelope via
+/// `decode_gossip_payload`) should be accepted, and whether it was a replay, given the current
+/// replay-window configuration. Returns `(data, accept, duplicate)`, mirroring the fields
+/// `run_swarm`'s `Gossipsub::Event::Message` handler reports back to gossipsub and to Python.
+///
+/// `is_explicit_id` messages (`publish_gossip_with_id`) are always accepted regardless of
+/// `replay_window`: they never carry a replay envelope in the first place, and gossipsub's own
+/// de-dup on the explicit id already does what the replay window would — see
+/// `publish_gossip_with_id`'s doc comment.
+fn classify_gossip_message(
+    raw_data: Vec<u8>,
+    is_explicit_id: bool,
+    replay_window: Option<Duration>,
+    now_unix: u64,
+    seen_replay_nonces: &mut HashMap<[u8; 16], Instant>,
+) -> (Vec<u8>, bool, bool) {
+    if is_explicit_id {
+        return (raw_data, true, false);
+    }
+    match replay_window {
+        Some(window) => match decode_replay_envelope(&raw_data) {
+            Some((timestamp, nonce, inner)) => {
+                let fresh = now_unix.abs_diff(timestamp) <= window.as_secs();
+                let replayed = seen_replay_nonces.contains_key(&nonce);
+                if fresh && !replayed {
+                    seen_replay_nonces.insert(nonce, Instant::now());
+                }
+                (inner.to_vec(), fresh && !replayed, replayed)
+            }
+            // No envelope at all (e.g. a peer not running replay protection): there's nothing
+            // to check freshness against, so reject rather than deliver an unprotected message
+            // while protection is enabled.
+            None => (raw_data, false, false),
+        },
+        // With no replay window set, gossipsub's `validate_messages()` (always on, see
+        // `behaviour::build`) is reported `Accept` immediately for every message, reproducing
+        // the plain auto-accept behavior this crate had before replay protection existed.
+        None => (raw_data, true, false),
+    }
+}
+
+/// Wraps `data` for `publish_gossip_with_id`, prepending `id` so `gossip_message_id` can use it
+/// directly as the gossipsub `MessageId` instead of deriving one from source+sequence number.
+fn encode_gossip_payload_with_id(id: &[u8], data: Vec<u8>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + 4 + id.len() + data.len());
+    out.push(EXPLICIT_ID_MARKER);
+    out.extend_from_slice(&(id.len() as u32).to_le_bytes());
+    out.extend_from_slice(id);
+    out.extend_from_slice(&data);
+    out
+}
+
+/// If `bytes` is an [`encode_gossip_payload_with_id`] envelope, splits it back into `(id,
+/// data)`.
+fn explicit_id_and_data(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&marker, rest) = bytes.split_first()?;
+    if marker != EXPLICIT_ID_MARKER {
+        return None;
+    }
+    let (id_len, rest) = rest.split_at_checked(4)?;
+    let id_len = u32::from_le_bytes(id_len.try_into().unwrap()) as usize;
+    rest.split_at_checked(id_len)
+}
+
+/// Derives the DHT key chunk `index` of a `put_large_record` value is stored under, distinct
+/// from the record's own key (which holds the manifest written by
+/// [`encode_large_record_manifest`]) so a plain `get_record` on it never returns partial chunk
+/// data instead of the manifest.
+fn large_record_chunk_key(key: &[u8], index: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(key.len() + 1 + 4);
+    out.extend_from_slice(key);
+    out.push(b'#');
+    out.extend_from_slice(&index.to_le_bytes());
+    out
+}
+
+/// The manifest record `put_large_record` stores under the value's own key: just the number of
+/// `large_record_chunk_key` records `get_large_record` needs to fetch and concatenate, in order.
+fn encode_large_record_manifest(total_chunks: u32) -> Vec<u8> {
+    total_chunks.to_le_bytes().to_vec()
+}
+
+/// Reverses [`encode_large_record_manifest`]. `None` if `bytes` isn't a manifest at all, e.g.
+/// `key` was written with a plain `put_record` instead.
+fn decode_large_record_manifest(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Wraps an outbound `rr` request payload with the same signed-envelope scheme
+/// [`encode_gossip_payload`] uses for gossip, so `set_require_rr_signature` can authenticate
+/// the sender of security-sensitive RPCs (e.g. block submission) at the framing level,
+/// independent of the transport identity noise already authenticates the connection with.
+fn encode_rr_payload(signing_key: Option<&libp2p::identity::Keypair>, data: Vec<u8>) -> PyResult<Vec<u8>> {
+    encode_gossip_payload(signing_key, data)
+}
+
+/// Reverses [`encode_rr_payload`]. See [`decode_gossip_payload`] for the exact semantics,
+/// including how malformed/unsigned envelopes are handled.
+fn decode_rr_payload(bytes: Vec<u8>) -> (Vec<u8>, Option<Vec<u8>>, Option<bool>) {
+    decode_gossip_payload(bytes)
+}
+
+/// Gossipsub `message_id_fn` used by every node: prefers an application-supplied id embedded
+/// by `publish_gossip_with_id`, falling back to the default source+sequence-number scheme for
+/// everything else (i.e. anything published via plain `publish_gossip`).
+pub(crate) fn gossip_message_id(message: &gossipsub::Message) -> gossipsub::MessageId {
+    if let Some((id, _)) = explicit_id_and_data(&message.data) {
+        return gossipsub::MessageId::from(id.to_vec());
+    }
+    let mut source_string = message
+        .source
+        .map(|peer_id| peer_id.to_base58())
+        .unwrap_or_else(|| "0".to_string());
+    source_string.push_str(&message.sequence_number.unwrap_or_default().to_string());
+    gossipsub::MessageId::from(source_string)
+}
+
+/// Builds the TLS config `wss` listeners/dials use to terminate the websocket's underlying
+/// TCP connection. Uses `cert_der`/`key_der` (DER-encoded X.509 / PKCS#8) if both are given;
+/// otherwise generates a fresh self-signed certificate, which is enough for nodes that only
+/// need encryption in transit rather than a browser-trusted certificate.
+fn build_wss_tls_config(
+    cert_der: Option<Vec<u8>>,
+    key_der: Option<Vec<u8>>,
+) -> PyResult<libp2p::websocket::tls::Config> {
+    let (cert_der, key_der) = match (cert_der, key_der) {
+        (Some(cert_der), Some(key_der)) => (cert_der, key_der),
+        _ => {
+            let certified_key = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+                .map_err(|e| P2pError::Transport(e.to_string()))?;
+            (certified_key.cert.der().to_vec(), certified_key.signing_key.serialize_der())
+        }
+    };
+    let key = libp2p::websocket::tls::PrivateKey::new(key_der);
+    let cert = libp2p::websocket::tls::Certificate::new(cert_der);
+    libp2p::websocket::tls::Config::new(key, [cert]).map_err(|e| P2pError::Transport(e.to_string()).into())
+}
+
+/// Builds the yamux multiplexer config shared by the TCP and websocket transports, applying
+/// whichever of `receive_window_size`/`max_buffer_size` the caller tuned. libp2p 0.56 no
+/// longer exposes mplex as a selectable multiplexer (it only ships yamux), so there is no
+/// "yamux vs mplex vs both" choice to offer here — this only covers the yamux tuning knobs.
+#[allow(deprecated)]
+fn build_yamux_config(
+    receive_window_size: Option<u32>,
+    max_buffer_size: Option<usize>,
+    max_num_streams: Option<usize>,
+) -> libp2p::yamux::Config {
+    let mut config = libp2p::yamux::Config::default();
+    if let Some(receive_window_size) = receive_window_size {
+        config.set_receive_window_size(receive_window_size);
+    }
+    if let Some(max_buffer_size) = max_buffer_size {
+        config.set_max_buffer_size(max_buffer_size);
+    }
+    if let Some(max_num_streams) = max_num_streams {
+        config.set_max_num_streams(max_num_streams);
+    }
+    config
+}
+
+/// Builds the noise handshake config, applying a custom prologue if one was set. Peers with
+/// mismatched prologues fail the handshake outright, which is the point: it lets operators
+/// partition networks that otherwise look identical at the protocol level.
+fn build_noise_config(
+    keypair: &libp2p::identity::Keypair,
+    prologue: Option<Vec<u8>>,
+) -> Result<libp2p::noise::Config, libp2p::noise::Error> {
+    let config = libp2p::noise::Config::new(keypair)?;
+    Ok(match prologue {
+        Some(prologue) => config.with_prologue(prologue),
+        None => config,
+    })
+}
+
+/// Drops every peer in `mdns_last_seen` not refreshed within `ttl`. `address_book` is shared
+/// across identify, mDNS, and explicit dial/persistent-peer config (see `run_swarm`'s
+/// `address_book` doc comment), so a stale mDNS sighting must only take back the addresses
+/// `mdns_addresses` recorded as coming from mDNS for that peer, not the whole merged entry —
+/// otherwise a peer that's also reachable via identify or a persistent-peer config loses those
+/// addresses too just because its LAN sighting expired. Also drops the `"mdns"` tag from
+/// `discovery_methods`, and removes a peer from either map entirely once it has no addresses or
+/// methods left. Returns the number of peers whose mDNS sighting expired.
+fn prune_expired_mdns_peers(
+    mdns_last_seen: &mut HashMap<PeerId, Instant>,
+    address_book: &mut HashMap<PeerId, std::collections::HashSet<Multiaddr>>,
+    mdns_addresses: &mut HashMap<PeerId, std::collections::HashSet<Multiaddr>>,
+    discovery_methods: &mut HashMap<PeerId, std::collections::HashSet<&'static str>>,
+    ttl: Duration,
+) -> usize {
+    let now = Instant::now();
+    let expired: Vec<PeerId> = mdns_last_seen
+        .iter()
+        .filter(|(_, last_seen)| now.duration_since(**last_seen) > ttl)
+        .map(|(peer_id, _)| *peer_id)
+        .collect();
+    for peer_id in &expired {
+        mdns_last_seen.remove(peer_id);
+        if let Some(stale_addrs) = mdns_addresses.remove(peer_id) {
+            if let Some(addrs) = address_book.get_mut(peer_id) {
+                addrs.retain(|addr| !stale_addrs.contains(addr));
+                if addrs.is_empty() {
+                    address_book.remove(peer_id);
+                }
+            }
+        }
+        if let Some(methods) = discovery_methods.get_mut(peer_id) {
+            methods.remove("mdns");
+            if methods.is_empty() {
+                discovery_methods.remove(peer_id);
+            }
+        }
+    }
+    expired.len()
+}
+
+/// Extracts the source IP from an inbound connection's `send_back_addr`, if present.
+fn multiaddr_ip(addr: &Multiaddr) -> Option<std::net::IpAddr> {
+    addr.iter().find_map(|proto| match proto {
+        Protocol::Ip4(ip) => Some(std::net::IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(std::net::IpAddr::V6(ip)),
+        _ => None,
+    })
+}
+
+/// One `get_discovered_peers` entry: `(peer_id, addresses, discovery_methods)`.
+type DiscoveredPeer = (String, Vec<String>, Vec<String>);
+
+/// A dial that `set_max_concurrent_dials` queued instead of starting immediately. Mirrors
+/// the two `Command` dial variants it was deferred from.
+enum QueuedDial {
+    Single(Multiaddr),
+    Known(PeerId, Vec<Multiaddr>),
+    Conditional(PeerId, Vec<Multiaddr>, libp2p::swarm::dial_opts::PeerCondition),
+}
+
+enum Command {
+    Dial(Multiaddr),
+    DialKnownAddresses(PeerId, Vec<Multiaddr>),
+    DialConditional(PeerId, Vec<Multiaddr>, libp2p::swarm::dial_opts::PeerCondition),
+    GetPendingDials(tokio::sync::oneshot::Sender<Vec<String>>),
+    SetMaxConcurrentDials(Option<usize>),
+    GetDialQueueDepth(tokio::sync::oneshot::Sender<usize>),
+    Listen(Multiaddr),
+    SetPersistentPeers(HashMap<PeerId, Multiaddr>),
+    Redial(PeerId),
+    SetKademliaMode(Option<libp2p::kad::Mode>),
+    GetKnownAddresses(PeerId, tokio::sync::oneshot::Sender<Vec<Multiaddr>>),
+    GetAddressBook(tokio::sync::oneshot::Sender<HashMap<String, Vec<String>>>),
+    GetDiscoveredPeers(tokio::sync::oneshot::Sender<Vec<DiscoveredPeer>>),
+    PutRecord(Vec<u8>, Vec<u8>, kad::Quorum, tokio::sync::oneshot::Sender<Result<(), String>>),
+    EnableRecordRepublish(Option<u64>),
+    GetRecord(Vec<u8>, usize, Duration, tokio::sync::oneshot::Sender<Result<Vec<Vec<u8>>, String>>),
+    SubscribeGossip(String, tokio::sync::oneshot::Sender<Result<bool, String>>),
+    UnsubscribeGossip(String, tokio::sync::oneshot::Sender<bool>),
+    SubscribeTopics(Vec<String>, tokio::sync::oneshot::Sender<Vec<(String, bool)>>),
+    UnsubscribeTopics(Vec<String>, tokio::sync::oneshot::Sender<Vec<(String, bool)>>),
+    GetSubscriptions(tokio::sync::oneshot::Sender<Vec<String>>),
+    GetDesiredSubscriptions(tokio::sync::oneshot::Sender<Vec<String>>),
+    PublishGossip(String, Vec<u8>, tokio::sync::oneshot::Sender<Result<(), String>>),
+    PublishGossipWithId(String, Vec<u8>, Vec<u8>, tokio::sync::oneshot::Sender<Result<(), String>>),
+    PublishToTopics(Vec<String>, Vec<u8>, tokio::sync::oneshot::Sender<Vec<(String, Option<String>)>>),
+    HasMeshPeers(String, tokio::sync::oneshot::Sender<bool>),
+    GetGossipsubAllPeers(tokio::sync::oneshot::Sender<Vec<(String, Vec<String>)>>),
+    GetGossipsubAllMeshPeers(tokio::sync::oneshot::Sender<Vec<String>>),
+    GetTopicHealth(String, tokio::sync::oneshot::Sender<TopicHealth>),
+    RemoveListener(libp2p::core::transport::ListenerId, tokio::sync::oneshot::Sender<bool>),
+    AddExternalAddress(Multiaddr),
+    RemoveExternalAddress(Multiaddr),
+    GetExternalAddresses(tokio::sync::oneshot::Sender<Vec<String>>),
+    GetListenAddrs(tokio::sync::oneshot::Sender<Vec<Multiaddr>>),
+    SetInterfaceExpansion(bool),
+    SetAddressFilter(AddressFilterMode),
+    SetMinAgentVersion(Option<semver::VersionReq>),
+    SetInboundRateLimit(Option<(f64, f64)>),
+    ListenBlocking(Multiaddr, Duration, tokio::sync::oneshot::Sender<Result<Vec<Multiaddr>, String>>),
+    SetRecordValidator(Option<Py<PyAny>>),
+    SetGossipSigningKey(Option<libp2p::identity::Keypair>),
+    SetRrSigningKey(Option<libp2p::identity::Keypair>),
+    SetRequireRrSignature(bool),
+    GetLastDialDuration(PeerId, tokio::sync::oneshot::Sender<Option<f64>>),
+    GetAverageDialDuration(tokio::sync::oneshot::Sender<Option<f64>>),
+    SetPublishRateLimit(String, Option<(f64, bool)>),
+    GetRoutingTableSize(tokio::sync::oneshot::Sender<usize>),
+    GetKBuckets(tokio::sync::oneshot::Sender<Vec<(u32, Vec<String>)>>),
+    GetClosestLocalPeers(Vec<u8>, usize, tokio::sync::oneshot::Sender<Vec<String>>),
+    BootstrapWith(Vec<(PeerId, Multiaddr)>, tokio::sync::oneshot::Sender<Result<(), String>>),
+    GetRoutingUpdateCount(tokio::sync::oneshot::Sender<u64>),
+    GetDhtStoreStats(tokio::sync::oneshot::Sender<DhtStoreStats>),
+    SetReplayWindow(Option<Duration>),
+    GetLiveGossipStats(tokio::sync::oneshot::Sender<HashMap<String, HashMap<String, u64>>>),
+    StartProviding(kad::RecordKey, tokio::sync::oneshot::Sender<Result<(), String>>),
+    StopProviding(kad::RecordKey),
+    GetProvidedKeys(tokio::sync::oneshot::Sender<Vec<Vec<u8>>>),
+    SendFile(PeerId, String, std::path::PathBuf, tokio::sync::oneshot::Sender<Result<u64, String>>),
+    GetTopicSubscribers(String, tokio::sync::oneshot::Sender<Vec<String>>),
+    SetPeerTtl(Option<Duration>),
+    PruneExpiredPeers(tokio::sync::oneshot::Sender<usize>),
+    TriggerAutonatProbe(Multiaddr),
+    GetReachabilityConfidence(tokio::sync::oneshot::Sender<usize>),
+    IsAddressReachable(Multiaddr, tokio::sync::oneshot::Sender<Option<bool>>),
+    GetPeerHealth(PeerId, tokio::sync::oneshot::Sender<f64>),
+    BanPeer(PeerId),
+    UnbanPeer(PeerId),
+    AllowPeer(PeerId),
+    DisallowPeer(PeerId),
+    ExportAccessLists(tokio::sync::oneshot::Sender<(Vec<String>, Vec<String>)>),
+    ImportAccessLists(Vec<PeerId>, Vec<PeerId>),
+    BlacklistGossipPeer(PeerId),
+    RemoveBlacklistedGossipPeer(PeerId),
+    SetGossipCacheCapacity(usize),
+    GetCachedGossipMessages(tokio::sync::oneshot::Sender<Vec<(String, Vec<u8>)>>),
+    GetGossipCacheBytes(tokio::sync::oneshot::Sender<usize>),
+    SetKeepAlivePeer(PeerId, bool),
+    GetKeepAlivePeers(tokio::sync::oneshot::Sender<Vec<String>>),
+    SendRrRequest(PeerId, String, Vec<u8>, Duration, tokio::sync::oneshot::Sender<Result<Vec<u8>, String>>),
+    RespondRrRequest(u64, Vec<u8>, tokio::sync::oneshot::Sender<Result<(), String>>),
+    /// Tells `run_swarm` to exit its event loop. Plain channel closure doesn't do this: the
+    /// driver thread holds its own clone of the command sender (for scheduling delayed
+    /// redials), so `recv()` never sees `None` on its own.
+    Shutdown,
+}
+
+/// Typed snapshot of a node's top-level status, returned by `Node.get_network_info_struct`.
+/// Unlike most of this crate's "get a bunch of stuff" methods, which return a plain dict,
+/// this one is a real pyclass with typed fields, so callers don't have to parse numbers back
+/// out of strings.
+#[pyclass]
+pub struct NetworkInfo {
+    #[pyo3(get)]
+    peer_id: String,
+    #[pyo3(get)]
+    num_listeners: usize,
+    #[pyo3(get)]
+    num_connected: usize,
+    #[pyo3(get)]
+    num_pending_dials: usize,
+    #[pyo3(get)]
+    running: bool,
+    #[pyo3(get)]
+    uptime_secs: u64,
+}
+
+/// Mesh diagnostic for one gossipsub topic, synthesizing the raw mesh-peer count against the
+/// configured bounds into an at-a-glance health signal. Backs `get_topic_health`.
+struct TopicHealth {
+    mesh_size: usize,
+    mesh_n_low: usize,
+    mesh_n_high: usize,
+    /// Peers this node would fall back to gossiping the next message to if the mesh were
+    /// empty. Always `0`: `libp2p-gossipsub`'s fanout table isn't exposed by its public API, so
+    /// this crate has nothing to report it from.
+    fanout_size: usize,
+    status: &'static str,
+}
+
+/// Snapshot of the Kademlia `MemoryStore`'s occupancy against its configured caps.
+struct DhtStoreStats {
+    record_count: usize,
+    max_records: usize,
+    provided_key_count: usize,
+    max_provided_keys: usize,
+    max_value_bytes: usize,
+}
+
+fn backoff_for_attempt(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY
+        .saturating_mul(1u32.checked_shl(attempt).unwrap_or(u32::MAX))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+/// Combines ping latency, live connection count, and disconnect history into a single
+/// `[0, 1]` health score, weighted towards latency since it's the most direct signal of
+/// an actually-usable link.
+fn peer_health_score(stats: Option<&PeerStats>, connection_count: usize) -> f64 {
+    let latency_score = match stats.and_then(|s| s.last_ping_rtt) {
+        Some(rtt) => (1.0 - (rtt.as_secs_f64() / 2.0).min(1.0)).max(0.0),
+        None => 0.5,
+    };
+    let connection_score = (connection_count as f64 / 2.0).min(1.0);
+    let disconnect_score = match stats {
+        Some(s) => 1.0 / (1.0 + f64::from(s.disconnect_count) * 0.2),
+        None => 1.0,
+    };
+    (0.5 * latency_score + 0.25 * connection_score + 0.25 * disconnect_score).clamp(0.0, 1.0)
+}
+
+/// A running libp2p node, driven on its own background thread.
+///
+/// The swarm is owned entirely by the driver thread; Python only ever talks to it
+/// through the `commands` channel and reads results back off the `events` queue, so
+/// there is no need to hold the GIL while the swarm is polled.
+#[pyclass]
+pub struct Node {
+    events: Arc<Mutex<EventQueue>>,
+    commands: mpsc::UnboundedSender<Command>,
+    local_peer_id: PeerId,
+    supported_protocols: Vec<String>,
+    listeners: Arc<Mutex<HashMap<String, libp2p::core::transport::ListenerId>>>,
+    /// Addresses (normalized via `Multiaddr`'s own `Display`) currently being listened on or
+    /// bound, checked by `listen`/`listen_blocking` before issuing a second `listen_on` for the
+    /// same address. Distinct from `listeners`, which only gains an entry once libp2p confirms
+    /// the bind with `NewListenAddr` — this set also covers the window between requesting a
+    /// listener and that confirmation arriving, where a duplicate call would otherwise queue a
+    /// second listener that fails asynchronously and invisibly.
+    active_listen_addrs: Arc<Mutex<std::collections::HashSet<String>>>,
+    connections: Arc<Mutex<HashMap<ConnectionId, ConnectionRecord>>>,
+    keypair: libp2p::identity::Keypair,
+    /// Handle to the swarm driver thread, taken by `close()`/`Drop` so both can join it
+    /// exactly once; `None` once it's already been shut down.
+    swarm_thread: Mutex<Option<std::thread::JoinHandle<()>>>,
+    /// When this `Node` was constructed, backing `get_network_info_struct`'s `uptime_secs`.
+    started_at: Instant,
+    /// Whether this node wired a Unix domain socket transport into its `SwarmBuilder` (set at
+    /// construction time by `Node.new`'s `enable_uds` flag), checked by `require_enabled_transport`
+    /// before `listen`/`listen_blocking` accept a `/unix/...` address.
+    enable_uds: bool,
+}
+
+/// The transports this node actually wires into its `SwarmBuilder`. QUIC is enabled as a
+/// Cargo feature on `libp2p` but isn't hooked up here yet, so it deliberately isn't listed.
+/// WebTransport (which needs QUIC as its prerequisite, plus `libp2p-webtransport`, which isn't
+/// a dependency of this crate at all) isn't listed for the same reason — see
+/// `require_enabled_transport`'s dedicated error for it. Unix domain sockets are opt-in via
+/// `Node.new(enable_uds=True)`, so `"unix"` deliberately isn't listed here either — see
+/// `require_enabled_transport`.
+const ENABLED_TRANSPORTS: &[&str] = &["tcp", "ws", "wss"];
+
+/// Which of `ENABLED_TRANSPORTS` (plus `"unix"`, gated separately by `enable_uds`) `addr` would
+/// need, or `None` if it doesn't match any of them (e.g. a `/quic-v1` address, or a bare
+/// `/p2p/<peerid>`). `Ws`/`Wss` always appear after `Tcp` in a real address, so checking them
+/// last lets them override the `"tcp"` classification a plain `/tcp/<port>` prefix would
+/// otherwise produce.
+fn transport_kind_for_addr(addr: &Multiaddr) -> Option<&'static str> {
+    let mut kind = None;
+    for protocol in addr.iter() {
+        match protocol {
+            Protocol::Tcp(_) => kind = Some("tcp"),
+            Protocol::Ws(_) => kind = Some("ws"),
+            Protocol::Wss(_) => kind = Some("wss"),
+            Protocol::Unix(_) => kind = Some("unix"),
+            _ => {}
+        }
+    }
+    kind
+}
+
+/// Claims `addr` (normalized via `Multiaddr`'s own `Display`) as currently being listened
+/// on, erroring if it's already claimed. Checked synchronously from `listen`/`listen_blocking`
+/// before the `Listen`/`ListenBlocking` command is even sent, so two back-to-back calls for the
+/// same address fail the second one immediately instead of quietly queuing a listener that
+/// then fails asynchronously on the swarm thread. The claim is released by the swarm thread
+/// once it learns the listener closed or errored (see `run_swarm`'s handling of
+/// `ListenerClosed`/`ListenerError`), or immediately if `listen_on` itself rejects the address.
+fn claim_listen_addr(active_listen_addrs: &Mutex<std::collections::HashSet<String>>, addr: &Multiaddr) -> PyResult<()> {
+    let mut active = active_listen_addrs.lock().unwrap();
+    if active.insert(addr.to_string()) {
+        Ok(())
+    } else {
+        Err(P2pError::Transport(format!("already listening on {addr}")).into())
+    }
+}
+
+fn require_enabled_transport(addr: &Multiaddr, enable_uds: bool) -> PyResult<()> {
+    if addr.iter().any(|p| matches!(p, Protocol::WebTransport)) {
+        // `libp2p-webtransport` isn't vendored in this build (and QUIC, its prerequisite, isn't
+        // wired into `ENABLED_TRANSPORTS` either — see its doc comment), so this is a deliberate
+        // gap rather than an address this node just doesn't recognize.
+        return Err(P2pError::InvalidMultiaddr(format!(
+            "{addr} requests WebTransport, which this build doesn't support (no QUIC/WebTransport transport is wired into the swarm)"
+        ))
+        .into());
+    }
+    match transport_kind_for_addr(addr) {
+        Some("unix") if !enable_uds => Err(P2pError::InvalidMultiaddr(format!(
+            "{addr} requests a Unix domain socket, which this node wasn't constructed with (pass enable_uds=True to Node.new)"
+        ))
+        .into()),
+        Some(_) => Ok(()),
+        None => Err(P2pError::InvalidMultiaddr(format!(
+            "{addr} does not match any enabled transport (available: {}{})",
+            ENABLED_TRANSPORTS.join(", "),
+            if enable_uds { ", unix" } else { "" },
+        ))
+        .into()),
+    }
+}
+
+/// Whether `addr` goes through a `/p2p-circuit` relay hop rather than reaching the peer
+/// directly.
+fn is_relayed_addr(addr: &Multiaddr) -> bool {
+    addr.iter().any(|p| matches!(p, Protocol::P2pCircuit))
+}
+
+/// Which listen/external addresses `set_address_filter` allows `get_external_addresses`
+/// (and everything built on the listen/external sets, e.g. `get_dialable_addresses`) to
+/// hand back. Also gates which addresses `run_swarm` confirms as external (see its
+/// `NewListenAddr`/`SetAddressFilter` handling), which is in turn the only thing identify
+/// actually advertises to remote peers (see `behaviour::build`'s `hide_listen_addrs`) — so a
+/// disallowed address never reaches identify or, transitively, a remote peer's Kademlia table,
+/// not just this node's own Python-facing getters.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum AddressFilterMode {
+    /// No filtering: every address is returned. The default, reproducing pre-existing behavior.
+    #[default]
+    All,
+    /// Drop loopback (`127.0.0.0/8`, `::1`) addresses only.
+    NoLoopback,
+    /// Keep only globally routable addresses, dropping loopback, private (RFC 1918/RFC 4193),
+    /// link-local, and unspecified (`0.0.0.0`/`::`) ones.
+    PublicOnly,
+}
+
+impl AddressFilterMode {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "all" => Ok(Self::All),
+            "no_loopback" => Ok(Self::NoLoopback),
+            "public_only" => Ok(Self::PublicOnly),
+            other => Err(P2pError::Other(format!("unknown address filter mode: {other}")).into()),
+        }
+    }
+
+    /// Whether `addr` passes this filter, based on its first (IP) protocol component.
+    /// Addresses with no IP component (e.g. `/p2p/<peer id>` alone) always pass, since there's
+    /// nothing to classify.
+    fn allows(&self, addr: &Multiaddr) -> bool {
+        let ip = addr.iter().next().and_then(|p| match p {
+            Protocol::Ip4(ip) => Some(std::net::IpAddr::V4(ip)),
+            Protocol::Ip6(ip) => Some(std::net::IpAddr::V6(ip)),
+            _ => None,
+        });
+        let Some(ip) = ip else { return true };
+        match self {
+            Self::All => true,
+            Self::NoLoopback => !ip.is_loopback(),
+            Self::PublicOnly => {
+                !ip.is_loopback() && !ip.is_unspecified() && !is_private_or_link_local(ip)
+            }
+        }
+    }
+}
+
+/// Whether `ip` is a private-use or link-local address per RFC 1918 (IPv4), RFC 4193 (IPv6
+/// unique local), or RFC 3927/RFC 4291 (link-local), none of which are reachable from outside
+/// the local network.
+fn is_private_or_link_local(ip: std::net::IpAddr) -> bool {
+    match ip {
+        std::net::IpAddr::V4(v4) => v4.is_private() || v4.is_link_local(),
+        std::net::IpAddr::V6(v6) => (v6.segments()[0] & 0xfe00) == 0xfc00 || v6.is_unicast_link_local(),
+    }
+}
+
+/// Replaces a wildcard `/ip4/0.0.0.0` or `/ip6/::` address with one concrete address per
+/// local, non-loopback network interface (keeping every other component, e.g. the port,
+/// unchanged); addresses that aren't wildcard are passed through as-is. Peers can't dial a
+/// wildcard address, so advertising it (e.g. via `get_dialable_addresses`) is never useful.
+fn expand_wildcard_addrs(addrs: Vec<Multiaddr>) -> Vec<Multiaddr> {
+    let mut interface_ips: Option<Vec<std::net::IpAddr>> = None;
+    let mut expanded = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let is_wildcard = addr.iter().next().is_some_and(|p| match p {
+            Protocol::Ip4(ip) => ip.is_unspecified(),
+            Protocol::Ip6(ip) => ip.is_unspecified(),
+            _ => false,
+        });
+        if !is_wildcard {
+            expanded.push(addr);
+            continue;
+        }
+        let ips = interface_ips.get_or_insert_with(|| {
+            if_addrs::get_if_addrs()
+                .map(|interfaces| {
+                    interfaces.into_iter().filter(|i| !i.is_loopback()).map(|i| i.ip()).collect()
+                })
+                .unwrap_or_default()
+        });
+        let wants_v6 = matches!(addr.iter().next(), Some(Protocol::Ip6(_)));
+        let rest: Vec<Protocol<'static>> = addr.into_iter().skip(1).map(|p| p.acquire()).collect();
+        for ip in ips.iter().filter(|ip| ip.is_ipv6() == wants_v6) {
+            let mut concrete = Multiaddr::empty();
+            concrete.push(match ip {
+                std::net::IpAddr::V4(v4) => Protocol::Ip4(*v4),
+                std::net::IpAddr::V6(v6) => Protocol::Ip6(*v6),
+            });
+            for protocol in &rest {
+                concrete.push(protocol.clone());
+            }
+            expanded.push(concrete);
+        }
+    }
+    expanded
+}
+
+/// Returns the peer id if `addr` consists of nothing but a `/p2p/<peerid>` component, i.e.
+/// it has no transport to dial on its own.
+fn peer_id_only(addr: &Multiaddr) -> Option<PeerId> {
+    let mut iter = addr.iter();
+    match (iter.next(), iter.next()) {
+        (Some(Protocol::P2p(peer_id)), None) => Some(peer_id),
+        _ => None,
+    }
+}
+
+fn connection_close_reason(cause: Option<ConnectionError>) -> String {
+    match cause {
+        None => "Disconnected".to_string(),
+        Some(ConnectionError::KeepAliveTimeout) => "KeepAliveTimeout".to_string(),
+        Some(ConnectionError::IO(err)) => err.to_string(),
+    }
+}
+
+/// Whether a connection closure was caused by exceeding `yamux_max_num_streams`
+/// (see `Node.new`). yamux surfaces this distinctly only when *this* side tried to open an
+/// outbound stream past the limit (`io::Error` wrapping yamux's "maximum number of streams
+/// reached"); a remote peer opening one *inbound* past the limit instead makes the yamux
+/// connection tear itself down with a protocol-error GoAway, which reaches here as a plain
+/// I/O error with no distinguishing text. So this only catches the outbound case — the
+/// honest alternative to pretending both are detectable.
+fn is_stream_limit_error(cause: &Option<ConnectionError>) -> bool {
+    matches!(cause, Some(ConnectionError::IO(err)) if err.to_string().contains("maximum number of streams reached"))
+}
+
+fn start_dial(
+    swarm: &mut Swarm<Behaviour>,
+    dial_started: &mut HashMap<ConnectionId, Instant>,
+    pending_dials: &mut HashMap<ConnectionId, Multiaddr>,
+    dial: QueuedDial,
+) {
+    match dial {
+        QueuedDial::Single(addr) => {
+            let opts: libp2p::swarm::dial_opts::DialOpts = addr.clone().into();
+            dial_started.insert(opts.connection_id(), Instant::now());
+            pending_dials.insert(opts.connection_id(), addr);
+            let _ = swarm.dial(opts);
+        }
+        QueuedDial::Known(peer_id, addrs) => {
+            let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer_id).addresses(addrs.clone()).build();
+            dial_started.insert(opts.connection_id(), Instant::now());
+            if let Some(addr) = addrs.into_iter().next() {
+                pending_dials.insert(opts.connection_id(), addr);
+            }
+            let _ = swarm.dial(opts);
+        }
+        QueuedDial::Conditional(peer_id, addrs, condition) => {
+            let opts =
+                libp2p::swarm::dial_opts::DialOpts::peer_id(peer_id).condition(condition).addresses(addrs.clone()).build();
+            dial_started.insert(opts.connection_id(), Instant::now());
+            if let Some(addr) = addrs.into_iter().next() {
+                pending_dials.insert(opts.connection_id(), addr);
+            }
+            let _ = swarm.dial(opts);
+        }
+    }
+}
+
+/// Starts queued dials until `max_concurrent_dials` in-flight dials are reached (or, with no
+/// limit set, drains the whole queue). Called both when a slot frees up and right after the
+/// limit itself changes, so lowering/removing the limit doesn't leave dials stuck queued.
+fn drain_dial_queue(
+    swarm: &mut Swarm<Behaviour>,
+    dial_started: &mut HashMap<ConnectionId, Instant>,
+    pending_dials: &mut HashMap<ConnectionId, Multiaddr>,
+    dial_queue: &mut std::collections::VecDeque<QueuedDial>,
+    max_concurrent_dials: Option<usize>,
+) {
+    loop {
+        if let Some(limit) = max_concurrent_dials {
+            if pending_dials.len() >= limit {
+                break;
+            }
+        }
+        match dial_queue.pop_front() {
+            Some(dial) => start_dial(swarm, dial_started, pending_dials, dial),
+            None => break,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_swarm(
+    mut swarm: Swarm<Behaviour>,
+    events: Arc<Mutex<EventQueue>>,
+    listeners: Arc<Mutex<HashMap<String, libp2p::core::transport::ListenerId>>>,
+    active_listen_addrs: Arc<Mutex<std::collections::HashSet<String>>>,
+    connections: Arc<Mutex<HashMap<ConnectionId, ConnectionRecord>>>,
+    gossipsub_max_transmit_size: usize,
+    topic_scheme: TopicScheme,
+    dht_store_config: kad::store::MemoryStoreConfig,
+    heartbeat_interval_secs: u64,
+    rr_max_retries: u32,
+    runtime_worker_threads: Option<usize>,
+    idle_connection_timeout_secs: u64,
+    commands_tx: mpsc::UnboundedSender<Command>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+) {
+    // `None` keeps the previous behaviour (a multi-thread runtime sized to all CPUs). `Some(0)`
+    // opts into a `new_current_thread` runtime instead, the cheapest option for embedding many
+    // nodes in one process (tests/simulations); `Some(n >= 1)` keeps multi-thread but caps it
+    // to `n` worker threads.
+    let mut runtime_builder = match runtime_worker_threads {
+        Some(0) => tokio::runtime::Builder::new_current_thread(),
+        Some(n) => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            builder.worker_threads(n);
+            builder
+        }
+        None => tokio::runtime::Builder::new_multi_thread(),
+    };
+    let runtime = runtime_builder
+        .enable_all()
+        .build()
+        .expect("failed to start tokio runtime for p2p node");
+
+    let mut persistent_peers: HashMap<PeerId, PersistentPeer> = HashMap::new();
+    let mut rate_limiter: Option<InboundRateLimiter> = None;
+    // Connections `rate_limiter` rejected at `IncomingConnection` time, keyed by connection id
+    // with the offending address, so they can actually be torn down once they reach
+    // `ConnectionEstablished` — `Swarm::close_connection` is a no-op for anything still in the
+    // pending pool, which is all a connection id from `IncomingConnection` ever is. Entries are
+    // removed here on `ConnectionEstablished` (closed then) or `IncomingConnectionError` (the
+    // handshake failed on its own, nothing left to close).
+    let mut rate_limited_connections: HashMap<ConnectionId, Multiaddr> = HashMap::new();
+    let mut pending_dials: HashMap<ConnectionId, Multiaddr> = HashMap::new();
+    // `None` (the default) dials everything immediately, as before. `Some(n)` caps how many
+    // dials (tracked via `pending_dials.len()`) are in flight at once; anything past that sits
+    // in `dial_queue` until a slot frees up, so bulk reconnection after a partition doesn't
+    // thunder-herd the local socket table.
+    let mut max_concurrent_dials: Option<usize> = None;
+    let mut dial_queue: std::collections::VecDeque<QueuedDial> = std::collections::VecDeque::new();
+    let mut subscribed_topics: std::collections::HashSet<String> = std::collections::HashSet::new();
+    // Topics a caller has asked to be subscribed to via `subscribe_gossip`/`subscribe_topics`,
+    // independent of whether the subscribe call actually succeeded. Unlike `subscribed_topics`
+    // (a mirror of gossipsub's own current state), this only shrinks on an explicit
+    // `unsubscribe_gossip`/`unsubscribe_topics` call, so a subscribe that failed transiently
+    // within this process (e.g. attempted before the swarm had any mesh peers) is retried below
+    // instead of being silently dropped. This does NOT survive a process restart on its own —
+    // see `get_desired_subscriptions`'s doc comment.
+    let mut desired_subscriptions: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut next_resubscribe_check = Instant::now();
+    let mut topic_subscribers: HashMap<gossipsub::TopicHash, std::collections::HashSet<PeerId>> = HashMap::new();
+    // `Sha256Topic` hashes can't be reversed back into the topic string the way
+    // `IdentTopic`'s can, so `GossipMessage` events would otherwise report an opaque hash for
+    // sha256-scheme networks. Populated everywhere `topic_scheme.hash()` is called on a topic
+    // name this node knows, so by the time a message for that hash arrives the name is on hand.
+    let mut topic_name_by_hash: HashMap<gossipsub::TopicHash, String> = HashMap::new();
+    // Recent gossip messages, oldest first, so a newly-subscribed peer can be replayed some
+    // history instead of only ever seeing messages published after it joined. A `VecDeque` so
+    // both ends are O(1): `push_back` on arrival, `pop_front` on eviction.
+    let mut gossip_cache: VecDeque<(String, Vec<u8>)> = VecDeque::new();
+    let mut gossip_cache_capacity: usize = DEFAULT_GOSSIP_CACHE_CAPACITY;
+    let mut mdns_last_seen: HashMap<PeerId, Instant> = HashMap::new();
+    // Addresses `address_book` learned specifically from mDNS, per peer, so
+    // `prune_expired_mdns_peers` can take back only those on TTL expiry rather than the whole
+    // (possibly identify- or persistent-peer-sourced) merged entry.
+    let mut mdns_addresses: HashMap<PeerId, std::collections::HashSet<Multiaddr>> = HashMap::new();
+    let mut peer_ttl: Option<Duration> = None;
+    // Whether `GetListenAddrs` expands a wildcard bind (`0.0.0.0`/`::`) into one concrete
+    // address per local, non-loopback interface. See `enable_interface_expansion`.
+    let mut interface_expansion = false;
+    // Set by `set_address_filter`. Applied to `GetListenAddrs`/`GetExternalAddresses` results,
+    // so a public node can avoid advertising loopback/private addresses via identify/Kademlia.
+    let mut address_filter = AddressFilterMode::default();
+    // Peers designated by `Node.keep_alive_peer`. On the maintenance tick below, each gets a
+    // fire-and-forget `rr` request roughly twice per `idle_connection_timeout_secs` (see
+    // `keepalive_interval`), which opens and closes a substream on its connection purely to
+    // reset libp2p's own idle timer — the request's payload never decodes as a known `rr`
+    // message on the receiving end, so it produces no visible event there. This is the "override
+    // the global idle timeout for specific peers" lever `keep_alive_peer`'s doc comment
+    // describes: everything else about the connection (mesh membership, ping, etc.) is
+    // untouched.
+    let mut keep_alive_peers: std::collections::HashSet<PeerId> = std::collections::HashSet::new();
+    let mut last_keepalive_sent: HashMap<PeerId, Instant> = HashMap::new();
+    let keepalive_interval = Duration::from_secs(idle_connection_timeout_secs.max(2) / 2);
+    let mut address_book: HashMap<PeerId, std::collections::HashSet<Multiaddr>> = HashMap::new();
+    // Which discovery source(s) have reported each peer, backing `get_discovered_peers`. Kept
+    // separate from `address_book` since the latter already merges addresses across sources and
+    // has no room left to record where each one came from.
+    let mut discovery_methods: HashMap<PeerId, std::collections::HashSet<&'static str>> = HashMap::new();
+    let mut routing_updates: u64 = 0;
+    let mut next_transfer_id: u64 = 0;
+    let mut outbound_transfers: HashMap<request_response::OutboundRequestId, OutboundTransfer> = HashMap::new();
+    let mut inbound_transfers: HashMap<(PeerId, u64), Vec<u8>> = HashMap::new();
+    let mut pending_listens: Vec<PendingListen> = Vec::new();
+    // Maps a listener still being tracked in `active_listen_addrs` back to its address, so
+    // `ListenerClosed`/`ListenerError` can clear the right entry without re-deriving it.
+    let mut listener_addrs_by_id: HashMap<libp2p::core::transport::ListenerId, String> = HashMap::new();
+    let mut record_validator: Option<Py<PyAny>> = None;
+    let mut gossip_signing_key: Option<libp2p::identity::Keypair> = None;
+    // Set by `set_replay_window`. When `Some`, outgoing `publish_gossip`/`publish_to_topics`
+    // messages are wrapped with `encode_replay_envelope`, and incoming messages must carry a
+    // matching envelope with a fresh, unseen nonce or they're rejected via
+    // `report_message_validation_result`. `None` (the default) leaves gossipsub's normal
+    // immediate-accept behavior untouched.
+    let mut replay_window: Option<Duration> = None;
+    // Nonces seen while `replay_window` is set, so a captured-and-resent message is caught even
+    // if its timestamp is still within the window. Swept on the 100ms tick using the same
+    // window, so this never grows past roughly one window's worth of traffic.
+    let mut seen_replay_nonces: HashMap<[u8; 16], Instant> = HashMap::new();
+    let mut gossip_stats: HashMap<String, TopicGossipStats> = HashMap::new();
+    // Addresses explicitly probed via `TriggerAutonatProbe`, in the order they were requested.
+    // Correlated with the resulting probe's id once the behaviour actually starts it, since
+    // `OutboundProbeEvent` doesn't carry the address for background-scheduled probes.
+    let mut pending_autonat_probes: VecDeque<Multiaddr> = VecDeque::new();
+    let mut autonat_probe_addrs: HashMap<autonat::ProbeId, Multiaddr> = HashMap::new();
+    // Per-address probe outcome backing `is_address_reachable`: successes from any probe,
+    // failures only from explicitly triggered ones (a background failure names no address).
+    let mut autonat_reachability: HashMap<Multiaddr, bool> = HashMap::new();
+    let mut rr_signing_key: Option<libp2p::identity::Keypair> = None;
+    // When set, inbound rr requests without a valid signature are rejected (no ack sent, so the
+    // sender's own retry/timeout handling from `Command::SendFile` kicks in) and reported as
+    // `NodeEvent::UnauthenticatedRequest` instead of being processed.
+    let mut require_rr_signature = false;
+    let mut dial_started: HashMap<ConnectionId, Instant> = HashMap::new();
+    let mut last_dial_durations: HashMap<PeerId, f64> = HashMap::new();
+    let mut dial_duration_total = 0.0f64;
+    let mut dial_duration_count: u64 = 0;
+    let mut publish_rate_limits: HashMap<String, PublishRateLimit> = HashMap::new();
+    let mut peer_stats: HashMap<PeerId, PeerStats> = HashMap::new();
+    let mut banned_peers: std::collections::HashSet<PeerId> = std::collections::HashSet::new();
+    // Non-empty means allowlist mode: only these peers (plus anyone already connected when
+    // the list was populated) may stay connected. Empty (the default) means no allowlist is
+    // in effect at all, not "allow no one".
+    let mut allowed_peers: std::collections::HashSet<PeerId> = std::collections::HashSet::new();
+    // Set by `set_min_agent_version`; peers whose identify `agent_version` doesn't parse as a
+    // semver satisfying this requirement are disconnected as soon as identify completes.
+    // Peers whose `agent_version` isn't a `<name>/<semver>` string at all are let through
+    // uncontested, since there's nothing to compare.
+    let mut min_agent_version: Option<semver::VersionReq> = None;
+    // Records this node has `put_record`-ed, so `enable_record_republish` can re-put them
+    // before they expire on remote nodes. Keyed/valued rather than a plain set of keys since
+    // republishing needs the value too, and the local `kad` store already discards it once the
+    // record's own TTL lapses there.
+    let mut published_records: HashMap<kad::RecordKey, Vec<u8>> = HashMap::new();
+    let mut record_republish: Option<(Duration, Instant)> = None;
+    // In-flight `get_record` queries: the requested quorum, every record value seen so far
+    // (kad's own replication means the same key can legitimately resolve to divergent values
+    // across replicas; duplicates are kept here so `quorum` counts responses, and
+    // `Node.get_record` drops them before returning), and the reply
+    // channel to fire once `quorum` values have arrived, the query itself finishes, or
+    // `deadline` passes.
+    let mut pending_get_records: HashMap<kad::QueryId, PendingGetRecord> = HashMap::new();
+    // In-flight `Node.request` calls, resolved from `Message::Response`/`OutboundFailure` or the
+    // 100ms tick's own deadline sweep, whichever comes first.
+    let mut pending_rr_requests: HashMap<request_response::OutboundRequestId, PendingRrRequest> = HashMap::new();
+    // Inbound generic requests (see `NodeEvent::IncomingRequest`) waiting on `Node.respond`,
+    // keyed by a crate-generated id since `request_response::InboundRequestId` has no public
+    // constructor to hand one back to Python and re-parse later.
+    let mut next_inbound_request_id: u64 = 0;
+    let mut pending_inbound_requests: HashMap<u64, request_response::ResponseChannel<Vec<u8>>> = HashMap::new();
+    let start = Instant::now();
+    let mut heartbeat_interval =
+        (heartbeat_interval_secs > 0).then(|| tokio::time::interval(Duration::from_secs(heartbeat_interval_secs)));
+
+    runtime.block_on(async move {
+        loop {
+            tokio::select! {
+                _ = async {
+                    match heartbeat_interval.as_mut() {
+                        Some(interval) => interval.tick().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let peer_count = connections.lock().unwrap().values().map(|r| r.peer_id).collect::<std::collections::HashSet<_>>().len();
+                    events.lock().unwrap().push_back(NodeEvent::Heartbeat {
+                        peer_count,
+                        uptime_secs: start.elapsed().as_secs_f64(),
+                    });
+                }
+                _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                    let now = Instant::now();
+                    if let Some((interval, next_due)) = record_republish {
+                        if now >= next_due {
+                            for (key, value) in &published_records {
+                                let record = kad::Record { key: key.clone(), value: value.clone(), publisher: None, expires: None };
+                                let _ = swarm.behaviour_mut().kad.put_record(record, kad::Quorum::One);
+                            }
+                            record_republish = Some((interval, now + interval));
+                        }
+                    }
+                    if let Some(ttl) = peer_ttl {
+                        prune_expired_mdns_peers(&mut mdns_last_seen, &mut address_book, &mut mdns_addresses, &mut discovery_methods, ttl);
+                    }
+                    if let Some(window) = replay_window {
+                        seen_replay_nonces.retain(|_, seen_at| now.duration_since(*seen_at) <= window);
+                    }
+                    pending_listens.retain_mut(|pending| {
+                        if now >= pending.deadline {
+                            if let Some(reply) = pending.reply.take() {
+                                let _ = reply.send(Err("timed out waiting for listener to bind".to_string()));
+                            }
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                    pending_get_records.retain(|_, pending| {
+                        if now >= pending.deadline {
+                            if let Some(reply) = pending.reply.take() {
+                                if pending.values.is_empty() {
+                                    let _ = reply.send(Err("get_record timed out".to_string()));
+                                } else {
+                                    let _ = reply.send(Ok(std::mem::take(&mut pending.values)));
+                                }
+                            }
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                    pending_rr_requests.retain(|_, pending| {
+                        if now >= pending.deadline {
+                            if let Some(reply) = pending.reply.take() {
+                                let _ = reply.send(Err("request timed out".to_string()));
+                            }
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                    if now >= next_resubscribe_check {
+                        next_resubscribe_check = now + Duration::from_secs(5);
+                        for topic_name in &desired_subscriptions {
+                            if !subscribed_topics.contains(topic_name) {
+                                topic_name_by_hash.insert(topic_scheme.hash(topic_name), topic_name.clone());
+                                if topic_scheme.subscribe(swarm.behaviour_mut(), topic_name).unwrap_or(false) {
+                                    subscribed_topics.insert(topic_name.clone());
+                                }
+                            }
+                        }
+                    }
+                    if !keep_alive_peers.is_empty() {
+                        let connected_peers: std::collections::HashSet<PeerId> =
+                            connections.lock().unwrap().values().map(|r| r.peer_id).collect();
+                        for peer_id in &keep_alive_peers {
+                            if !connected_peers.contains(peer_id) {
+                                continue;
+                            }
+                            let due = last_keepalive_sent
+                                .get(peer_id)
+                                .is_none_or(|sent| now.duration_since(*sent) >= keepalive_interval);
+                            if due {
+                                if let Ok(payload) = encode_rr_payload(rr_signing_key.as_ref(), Vec::new()) {
+                                    swarm.behaviour_mut().rr.send_request(peer_id, payload);
+                                }
+                                last_keepalive_sent.insert(*peer_id, now);
+                            }
+                        }
+                    }
+                }
+                event = swarm.select_next_some() => {
+                    if let SwarmEvent::NewListenAddr { listener_id, address } = &event {
+                        listeners.lock().unwrap().insert(address.to_string(), *listener_id);
+                        // Identify only advertises confirmed external addresses (see
+                        // `behaviour::build`'s `hide_listen_addrs`), and this is what confirms
+                        // them, so a listen address that fails `address_filter` here never
+                        // reaches identify or, transitively, a remote peer's Kademlia table.
+                        if address_filter.allows(address) {
+                            swarm.add_external_address(address.clone());
+                        }
+                        pending_listens.retain_mut(|pending| {
+                            if pending.listener_id == *listener_id {
+                                if let Some(reply) = pending.reply.take() {
+                                    let _ = reply.send(Ok(vec![address.clone()]));
+                                }
+                                false
+                            } else {
+                                true
+                            }
+                        });
+                    }
+                    if let SwarmEvent::ListenerClosed { listener_id, .. } = &event {
+                        if let Some(addr) = listener_addrs_by_id.remove(listener_id) {
+                            active_listen_addrs.lock().unwrap().remove(&addr);
+                        }
+                    }
+                    if let SwarmEvent::ListenerError { listener_id, .. } = &event {
+                        if let Some(addr) = listener_addrs_by_id.remove(listener_id) {
+                            active_listen_addrs.lock().unwrap().remove(&addr);
+                        }
+                    }
+                    if let SwarmEvent::IncomingConnection { connection_id, send_back_addr, .. } = &event {
+                        if let Some(limiter) = rate_limiter.as_mut() {
+                            let allowed = match multiaddr_ip(send_back_addr) {
+                                Some(ip) => limiter.allow(ip),
+                                None => true,
+                            };
+                            if !allowed {
+                                // Can't close yet: this connection id is still in the pending
+                                // pool, not the established one `Swarm::close_connection` looks
+                                // at. Closed once it actually reaches `ConnectionEstablished`.
+                                rate_limited_connections.insert(*connection_id, send_back_addr.clone());
+                            }
+                        }
+                    }
+                    if let SwarmEvent::IncomingConnectionError { connection_id, .. } = &event {
+                        rate_limited_connections.remove(connection_id);
+                    }
+                    if let SwarmEvent::Behaviour(BehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. })) = &event {
+                        let certified = info.signed_peer_record.is_some();
+                        if certified {
+                            for addr in &info.listen_addrs {
+                                swarm.behaviour_mut().kad.add_address(peer_id, addr.clone());
+                            }
+                        }
+                        address_book.entry(*peer_id).or_default().extend(info.listen_addrs.iter().cloned());
+                        discovery_methods.entry(*peer_id).or_default().insert("identify");
+                        events.lock().unwrap().push_back(NodeEvent::IdentifyReceived {
+                            peer_id: peer_id.to_string(),
+                            listen_addrs: info.listen_addrs.iter().map(|a| a.to_string()).collect(),
+                            protocols: info.protocols.iter().map(|p| p.to_string()).collect(),
+                            certified,
+                        });
+                        if let Some(req) = min_agent_version.as_ref() {
+                            let parsed_version =
+                                info.agent_version.rsplit_once('/').map(|(_, v)| v).and_then(|v| semver::Version::parse(v).ok());
+                            if let Some(version) = parsed_version {
+                                if !req.matches(&version) {
+                                    let _ = swarm.disconnect_peer_id(*peer_id);
+                                    events.lock().unwrap().push_back(NodeEvent::IncompatiblePeer {
+                                        peer_id: peer_id.to_string(),
+                                        agent_version: info.agent_version.clone(),
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    if let SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns_event)) = &event {
+                        match mdns_event {
+                            libp2p::mdns::Event::Discovered(discovered) => {
+                                for (peer_id, addr) in discovered {
+                                    address_book.entry(*peer_id).or_default().insert(addr.clone());
+                                    mdns_addresses.entry(*peer_id).or_default().insert(addr.clone());
+                                    discovery_methods.entry(*peer_id).or_default().insert("mdns");
+                                    mdns_last_seen.insert(*peer_id, Instant::now());
+                                }
+                            }
+                            libp2p::mdns::Event::Expired(expired) => {
+                                for (peer_id, addr) in expired {
+                                    if let Some(addrs) = address_book.get_mut(peer_id) {
+                                        addrs.remove(addr);
+                                    }
+                                    if let Some(addrs) = mdns_addresses.get_mut(peer_id) {
+                                        addrs.remove(addr);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if let SwarmEvent::Behaviour(BehaviourEvent::Kad(kad::Event::RoutingUpdated { peer, addresses, is_new_peer, .. })) = &event {
+                        address_book.entry(*peer).or_default().extend(addresses.iter().cloned());
+                        discovery_methods.entry(*peer).or_default().insert("kad");
+                        routing_updates += 1;
+                        events.lock().unwrap().push_back(NodeEvent::RoutingUpdated {
+                            peer_id: peer.to_string(),
+                            is_new_peer: *is_new_peer,
+                        });
+                    }
+                    if let SwarmEvent::Behaviour(BehaviourEvent::Kad(kad::Event::InboundRequest { request })) = &event {
+                        match request {
+                            kad::InboundRequest::PutRecord { record: Some(record), .. } => {
+                                let accepted = match record_validator.as_ref() {
+                                    Some(callback) => Python::attach(|py| {
+                                        callback
+                                            .call1(py, (record.key.as_ref().to_vec(), record.value.clone()))
+                                            .and_then(|result| result.extract::<bool>(py))
+                                            .unwrap_or(false)
+                                    }),
+                                    None => true,
+                                };
+                                if accepted {
+                                    let _ = swarm.behaviour_mut().kad.store_mut().put(record.clone());
+                                }
+                            }
+                            kad::InboundRequest::AddProvider { record: Some(record) } => {
+                                let _ = swarm.behaviour_mut().kad.store_mut().add_provider(record.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let SwarmEvent::Behaviour(BehaviourEvent::Kad(kad::Event::OutboundQueryProgressed {
+                        result: kad::QueryResult::PutRecord(result),
+                        stats,
+                        ..
+                    })) = &event
+                    {
+                        let (key, success, num_nodes, error) = match result {
+                            Ok(kad::PutRecordOk { key }) => (key.as_ref().to_vec(), true, stats.num_successes(), None),
+                            Err(e @ kad::PutRecordError::QuorumFailed { key, success, .. })
+                            | Err(e @ kad::PutRecordError::Timeout { key, success, .. }) => {
+                                (key.as_ref().to_vec(), false, success.len() as u32, Some(e.to_string()))
+                            }
+                        };
+                        events.lock().unwrap().push_back(NodeEvent::PutRecordResult { key, success, num_nodes, error });
+                    }
+                    if let SwarmEvent::Behaviour(BehaviourEvent::Kad(kad::Event::OutboundQueryProgressed {
+                        id,
+                        result: kad::QueryResult::GetRecord(result),
+                        step,
+                        ..
+                    })) = &event
+                    {
+                        let finished = step.last;
+                        match result {
+                            Ok(kad::GetRecordOk::FoundRecord(peer_record)) => {
+                                let done = if let Some(pending) = pending_get_records.get_mut(id) {
+                                    pending.values.push(peer_record.record.value.clone());
+                                    pending.values.len() >= pending.quorum || finished
+                                } else {
+                                    false
+                                };
+                                if done {
+                                    if let Some(mut pending) = pending_get_records.remove(id) {
+                                        if let Some(reply) = pending.reply.take() {
+                                            let _ = reply.send(Ok(pending.values));
+                                        }
+                                    }
+                                }
+                            }
+                            Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => {
+                                if let Some(mut pending) = pending_get_records.remove(id) {
+                                    if let Some(reply) = pending.reply.take() {
+                                        let _ = reply.send(Ok(pending.values));
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                if let Some(mut pending) = pending_get_records.remove(id) {
+                                    // A quorum/timeout failure after some records were already
+                                    // found is still a usable partial result.
+                                    if let Some(reply) = pending.reply.take() {
+                                        if pending.values.is_empty() {
+                                            let _ = reply.send(Err(e.to_string()));
+                                        } else {
+                                            let _ = reply.send(Ok(pending.values));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if let SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, .. } = &event {
+                        let access_denied = banned_peers.contains(peer_id)
+                            || (!allowed_peers.is_empty() && !allowed_peers.contains(peer_id));
+                        if access_denied {
+                            swarm.close_connection(*connection_id);
+                        }
+                        if let Some(address) = rate_limited_connections.remove(connection_id) {
+                            swarm.close_connection(*connection_id);
+                            events.lock().unwrap().push_back(NodeEvent::RateLimited { address: address.to_string() });
+                        }
+                        pending_dials.remove(connection_id);
+                        drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, max_concurrent_dials);
+                        if let Some(started) = dial_started.remove(connection_id) {
+                            let elapsed = started.elapsed().as_secs_f64();
+                            last_dial_durations.insert(*peer_id, elapsed);
+                            dial_duration_total += elapsed;
+                            dial_duration_count += 1;
+                        }
+                        if let Some(peer) = persistent_peers.get_mut(peer_id) {
+                            peer.attempt = 0;
+                        }
+                        connections.lock().unwrap().insert(*connection_id, ConnectionRecord {
+                            peer_id: *peer_id,
+                            remote_addr: endpoint.get_remote_address().to_string(),
+                            direction: if endpoint.is_dialer() { "outbound" } else { "inbound" },
+                            opened_at: Instant::now(),
+                            relayed: is_relayed_addr(endpoint.get_remote_address()),
+                        });
+                    }
+                    if let SwarmEvent::ConnectionClosed { peer_id, connection_id, .. } = &event {
+                        connections.lock().unwrap().remove(connection_id);
+                        for subscribers in topic_subscribers.values_mut() {
+                            subscribers.remove(peer_id);
+                        }
+                        last_keepalive_sent.remove(peer_id);
+                        peer_stats.entry(*peer_id).or_default().disconnect_count += 1;
+                        if let Some(peer) = persistent_peers.get_mut(peer_id) {
+                            let attempt = peer.attempt;
+                            peer.attempt = peer.attempt.saturating_add(1);
+                            let delay = backoff_for_attempt(attempt);
+                            let redial_peer = *peer_id;
+                            let redial_tx = commands_tx.clone();
+                            tokio::spawn(async move {
+                                tokio::time::sleep(delay).await;
+                                let _ = redial_tx.send(Command::Redial(redial_peer));
+                            });
+                            events.lock().unwrap().push_back(NodeEvent::ReconnectScheduled {
+                                peer_id: peer_id.to_string(),
+                                attempt: attempt + 1,
+                                backoff_secs: delay.as_secs(),
+                            });
+                        }
+                    }
+                    if let SwarmEvent::OutgoingConnectionError { connection_id, .. } = &event {
+                        pending_dials.remove(connection_id);
+                        dial_started.remove(connection_id);
+                        drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, max_concurrent_dials);
+                    }
+                    match event {
+                        SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Subscribed { peer_id, topic })) => {
+                            topic_subscribers.entry(topic).or_default().insert(peer_id);
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Unsubscribed { peer_id, topic })) => {
+                            if let Some(subscribers) = topic_subscribers.get_mut(&topic) {
+                                subscribers.remove(&peer_id);
+                            }
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                            propagation_source,
+                            message_id,
+                            message,
+                        })) => {
+                            // Checked against the raw wire bytes before `decode_gossip_payload` strips
+                            // the marker, so `publish_gossip_with_id` messages can be recognized below
+                            // regardless of whether they also carry a signing envelope.
+                            let is_explicit_id = message.data.first() == Some(&EXPLICIT_ID_MARKER);
+                            let (raw_data, signer_pubkey, signer_verified) = decode_gossip_payload(message.data);
+                            let now_unix = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs();
+                            let (data, accept, duplicate) =
+                                classify_gossip_message(raw_data, is_explicit_id, replay_window, now_unix, &mut seen_replay_nonces);
+                            swarm.behaviour_mut().gossipsub.report_message_validation_result(
+                                &message_id,
+                                &propagation_source,
+                                if accept { gossipsub::MessageAcceptance::Accept } else { gossipsub::MessageAcceptance::Reject },
+                            );
+                            // Falls back to the raw hash when it's unrecognized (e.g. a sha256-scheme
+                            // topic this node never itself subscribed to or published on), since
+                            // that's the best this node can do without the pre-image.
+                            let topic = topic_name_by_hash
+                                .get(&message.topic)
+                                .cloned()
+                                .unwrap_or_else(|| message.topic.to_string());
+                            let stats = gossip_stats.entry(topic.clone()).or_default();
+                            if accept {
+                                stats.received += 1;
+                            } else if duplicate {
+                                stats.duplicates_rejected += 1;
+                            } else {
+                                stats.validation_failures += 1;
+                            }
+                            if accept {
+                                gossip_cache.push_back((topic.clone(), data.clone()));
+                                while gossip_cache.len() > gossip_cache_capacity {
+                                    gossip_cache.pop_front();
+                                }
+                                events.lock().unwrap().push_back(NodeEvent::GossipMessage {
+                                    topic,
+                                    data,
+                                    source: message.source.map(|p| p.to_string()),
+                                    signer_pubkey,
+                                    signer_verified,
+                                });
+                            }
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Rr(request_response::Event::Message {
+                            peer,
+                            message: request_response::Message::Request { request, channel, .. },
+                            ..
+                        })) => {
+                            let (request, signer_pubkey, signer_verified) = decode_rr_payload(request);
+                            let authenticated = !require_rr_signature || signer_verified == Some(true);
+                            if !authenticated {
+                                events.lock().unwrap().push_back(NodeEvent::UnauthenticatedRequest {
+                                    peer_id: peer.to_string(),
+                                    reason: if signer_pubkey.is_none() {
+                                        "missing signature".to_string()
+                                    } else {
+                                        "invalid signature".to_string()
+                                    },
+                                });
+                            } else if let Ok(chunk) = transfer::decode_chunk(&request) {
+                                let buf = inbound_transfers.entry((peer, chunk.transfer_id)).or_default();
+                                buf.extend_from_slice(&chunk.data);
+                                let complete = chunk.seq == chunk.total;
+                                let data = if complete { inbound_transfers.remove(&(peer, chunk.transfer_id)) } else { None };
+                                let ack = transfer::encode_ack(&transfer::FileAck {
+                                    transfer_id: chunk.transfer_id,
+                                    seq: chunk.seq,
+                                });
+                                let _ = swarm.behaviour_mut().rr.send_response(channel, ack);
+                                events.lock().unwrap().push_back(NodeEvent::FileTransferProgress {
+                                    transfer_id: chunk.transfer_id,
+                                    peer_id: peer.to_string(),
+                                    protocol: chunk.protocol.clone(),
+                                    seq: chunk.seq,
+                                    total: chunk.total,
+                                    direction: "inbound".to_string(),
+                                    complete,
+                                    data,
+                                });
+                            } else if let Ok(rr_request) = transfer::decode_rr_request(&request) {
+                                next_inbound_request_id += 1;
+                                let request_id = next_inbound_request_id;
+                                pending_inbound_requests.insert(request_id, channel);
+                                events.lock().unwrap().push_back(NodeEvent::IncomingRequest {
+                                    request_id,
+                                    peer_id: peer.to_string(),
+                                    protocol: rr_request.protocol,
+                                    data: rr_request.data,
+                                });
+                            }
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Rr(request_response::Event::Message {
+                            message: request_response::Message::Response { request_id, response },
+                            ..
+                        })) => {
+                            if let Some(mut transfer) = outbound_transfers.remove(&request_id) {
+                                if transfer::decode_ack(&response).is_some() {
+                                    if transfer.seq == transfer.total {
+                                        events.lock().unwrap().push_back(NodeEvent::FileTransferProgress {
+                                            transfer_id: transfer.transfer_id,
+                                            peer_id: transfer.peer_id.to_string(),
+                                            protocol: transfer.protocol.clone(),
+                                            seq: transfer.seq,
+                                            total: transfer.total,
+                                            direction: "outbound".to_string(),
+                                            complete: true,
+                                            data: None,
+                                        });
+                                    } else {
+                                        transfer.seq += 1;
+                                        transfer.retries_used = 0;
+                                        let mut data = vec![0u8; transfer::CHUNK_SIZE];
+                                        let read = std::io::Read::read(&mut transfer.file, &mut data).unwrap_or(0);
+                                        data.truncate(read);
+                                        transfer.chunk_data = data.clone();
+                                        let chunk = FileChunk {
+                                            transfer_id: transfer.transfer_id,
+                                            protocol: transfer.protocol.clone(),
+                                            seq: transfer.seq,
+                                            total: transfer.total,
+                                            data,
+                                        };
+                                        events.lock().unwrap().push_back(NodeEvent::FileTransferProgress {
+                                            transfer_id: transfer.transfer_id,
+                                            peer_id: transfer.peer_id.to_string(),
+                                            protocol: transfer.protocol.clone(),
+                                            seq: transfer.seq,
+                                            total: transfer.total,
+                                            direction: "outbound".to_string(),
+                                            complete: false,
+                                            data: None,
+                                        });
+                                        let payload = encode_rr_payload(rr_signing_key.as_ref(), transfer::encode_chunk(&chunk))
+                                            .expect("rr payload signing is infallible");
+                                        let new_request_id = swarm.behaviour_mut().rr.send_request(&transfer.peer_id, payload);
+                                        outbound_transfers.insert(new_request_id, transfer);
+                                    }
+                                }
+                            } else if let Some(mut pending) = pending_rr_requests.remove(&request_id) {
+                                if let Some(reply) = pending.reply.take() {
+                                    let _ = reply.send(Ok(response));
+                                }
+                            }
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Rr(request_response::Event::OutboundFailure {
+                            request_id,
+                            error,
+                            ..
+                        })) => {
+                            if let Some(mut transfer) = outbound_transfers.remove(&request_id) {
+                                if transfer.retries_used < rr_max_retries {
+                                    transfer.retries_used += 1;
+                                    let chunk = FileChunk {
+                                        transfer_id: transfer.transfer_id,
+                                        protocol: transfer.protocol.clone(),
+                                        seq: transfer.seq,
+                                        total: transfer.total,
+                                        data: transfer.chunk_data.clone(),
+                                    };
+                                    let payload = encode_rr_payload(rr_signing_key.as_ref(), transfer::encode_chunk(&chunk))
+                                        .expect("rr payload signing is infallible");
+                                    let new_request_id = swarm.behaviour_mut().rr.send_request(&transfer.peer_id, payload);
+                                    outbound_transfers.insert(new_request_id, transfer);
+                                } else {
+                                    events.lock().unwrap().push_back(NodeEvent::OutboundFailure {
+                                        transfer_id: transfer.transfer_id,
+                                        peer_id: transfer.peer_id.to_string(),
+                                        error: error.to_string(),
+                                        retries: transfer.retries_used,
+                                    });
+                                }
+                            } else if let Some(mut pending) = pending_rr_requests.remove(&request_id) {
+                                if let Some(reply) = pending.reply.take() {
+                                    let _ = reply.send(Err(error.to_string()));
+                                }
+                            }
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event { peer, result: Ok(rtt), .. })) => {
+                            peer_stats.entry(peer).or_default().last_ping_rtt = Some(rtt);
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Autonat(autonat::Event::InboundProbe(probe_event))) => {
+                            if let Some(node_event) = translate_inbound_probe(probe_event) {
+                                events.lock().unwrap().push_back(node_event);
+                            }
+                        }
+                        SwarmEvent::Behaviour(BehaviourEvent::Autonat(autonat::Event::OutboundProbe(probe_event))) => {
+                            match &probe_event {
+                                autonat::OutboundProbeEvent::Request { probe_id, .. } => {
+                                    if let Some(addr) = pending_autonat_probes.pop_front() {
+                                        autonat_probe_addrs.insert(*probe_id, addr);
+                                    }
+                                }
+                                autonat::OutboundProbeEvent::Response { probe_id, address, .. } => {
+                                    autonat_probe_addrs.remove(probe_id);
+                                    autonat_reachability.insert(address.clone(), true);
+                                }
+                                autonat::OutboundProbeEvent::Error { probe_id, .. } => {
+                                    if let Some(addr) = autonat_probe_addrs.remove(probe_id) {
+                                        autonat_reachability.insert(addr, false);
+                                    }
+                                }
+                            }
+                            if let Some(node_event) = translate_outbound_probe(probe_event) {
+                                events.lock().unwrap().push_back(node_event);
+                            }
+                        }
+                        other => {
+                            if let Some(node_event) = translate(other) {
+                                events.lock().unwrap().push_back(node_event);
+                            }
+                        }
+                    }
+                }
+                cmd = commands.recv() => {
+                    match cmd {
+                        Some(Command::Dial(addr)) => {
+                            match max_concurrent_dials {
+                                Some(limit) if pending_dials.len() >= limit => {
+                                    dial_queue.push_back(QueuedDial::Single(addr));
+                                }
+                                _ => start_dial(&mut swarm, &mut dial_started, &mut pending_dials, QueuedDial::Single(addr)),
+                            }
+                        }
+                        Some(Command::DialKnownAddresses(peer_id, addrs)) => {
+                            match max_concurrent_dials {
+                                Some(limit) if pending_dials.len() >= limit => {
+                                    dial_queue.push_back(QueuedDial::Known(peer_id, addrs));
+                                }
+                                _ => start_dial(&mut swarm, &mut dial_started, &mut pending_dials, QueuedDial::Known(peer_id, addrs)),
+                            }
+                        }
+                        Some(Command::DialConditional(peer_id, addrs, condition)) => {
+                            match max_concurrent_dials {
+                                Some(limit) if pending_dials.len() >= limit => {
+                                    dial_queue.push_back(QueuedDial::Conditional(peer_id, addrs, condition));
+                                }
+                                _ => start_dial(&mut swarm, &mut dial_started, &mut pending_dials, QueuedDial::Conditional(peer_id, addrs, condition)),
+                            }
+                        }
+                        Some(Command::GetPendingDials(reply)) => {
+                            let addrs = pending_dials.values().map(|a| a.to_string()).collect();
+                            let _ = reply.send(addrs);
+                        }
+                        Some(Command::SetMaxConcurrentDials(limit)) => {
+                            max_concurrent_dials = limit;
+                            drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, max_concurrent_dials);
+                        }
+                        Some(Command::GetDialQueueDepth(reply)) => {
+                            let _ = reply.send(dial_queue.len());
+                        }
+                        Some(Command::GetKnownAddresses(peer_id, reply)) => {
+                            let addrs = address_book.get(&peer_id).cloned().unwrap_or_default().into_iter().collect();
+                            let _ = reply.send(addrs);
+                        }
+                        Some(Command::GetAddressBook(reply)) => {
+                            let book = address_book
+                                .iter()
+                                .map(|(peer_id, addrs)| (peer_id.to_string(), addrs.iter().map(|a| a.to_string()).collect()))
+                                .collect();
+                            let _ = reply.send(book);
+                        }
+                        Some(Command::GetDiscoveredPeers(reply)) => {
+                            let peers = address_book
+                                .iter()
+                                .map(|(peer_id, addrs)| {
+                                    let mut addrs: Vec<String> = addrs.iter().map(|a| a.to_string()).collect();
+                                    addrs.sort();
+                                    let mut methods: Vec<String> = discovery_methods
+                                        .get(peer_id)
+                                        .into_iter()
+                                        .flatten()
+                                        .map(|m| m.to_string())
+                                        .collect();
+                                    methods.sort();
+                                    (peer_id.to_string(), addrs, methods)
+                                })
+                                .collect();
+                            let _ = reply.send(peers);
+                        }
+                        Some(Command::PutRecord(key, value, quorum, reply)) => {
+                            let record_key = kad::RecordKey::new(&key);
+                            let record = kad::Record { key: record_key.clone(), value: value.clone(), publisher: None, expires: None };
+                            match swarm.behaviour_mut().kad.put_record(record, quorum) {
+                                Ok(_query_id) => {
+                                    published_records.insert(record_key, value);
+                                    let _ = reply.send(Ok(()));
+                                }
+                                Err(e) => {
+                                    let _ = reply.send(Err(e.to_string()));
+                                }
+                            }
+                        }
+                        Some(Command::GetRecord(key, quorum, timeout, reply)) => {
+                            let query_id = swarm.behaviour_mut().kad.get_record(kad::RecordKey::new(&key));
+                            pending_get_records.insert(query_id, PendingGetRecord {
+                                quorum: quorum.max(1),
+                                values: Vec::new(),
+                                deadline: Instant::now() + timeout,
+                                reply: Some(reply),
+                            });
+                        }
+                        Some(Command::EnableRecordRepublish(interval_secs)) => {
+                            record_republish = match interval_secs {
+                                Some(secs) if secs > 0 => {
+                                    let interval = Duration::from_secs(secs);
+                                    Some((interval, Instant::now() + interval))
+                                }
+                                _ => None,
+                            };
+                        }
+                        Some(Command::Listen(addr)) => {
+                            let key = addr.to_string();
+                            match swarm.listen_on(addr) {
+                                Ok(listener_id) => {
+                                    listener_addrs_by_id.insert(listener_id, key);
+                                }
+                                Err(_) => {
+                                    active_listen_addrs.lock().unwrap().remove(&key);
+                                }
+                            }
+                        }
+                        Some(Command::ListenBlocking(addr, timeout, reply)) => {
+                            let key = addr.to_string();
+                            match swarm.listen_on(addr) {
+                                Ok(listener_id) => {
+                                    listener_addrs_by_id.insert(listener_id, key);
+                                    pending_listens.push(PendingListen {
+                                        listener_id,
+                                        deadline: Instant::now() + timeout,
+                                        reply: Some(reply),
+                                    });
+                                }
+                                Err(e) => {
+                                    active_listen_addrs.lock().unwrap().remove(&key);
+                                    let _ = reply.send(Err(e.to_string()));
+                                }
+                            }
+                        }
+                        Some(Command::SubscribeGossip(topic_name, reply)) => {
+                            desired_subscriptions.insert(topic_name.clone());
+                            topic_name_by_hash.insert(topic_scheme.hash(&topic_name), topic_name.clone());
+                            let result = topic_scheme.subscribe(swarm.behaviour_mut(), &topic_name).map_err(|e| e.to_string());
+                            if matches!(result, Ok(true)) {
+                                subscribed_topics.insert(topic_name);
+                            }
+                            let _ = reply.send(result);
+                        }
+                        Some(Command::UnsubscribeGossip(topic_name, reply)) => {
+                            desired_subscriptions.remove(&topic_name);
+                            let unsubscribed = topic_scheme.unsubscribe(swarm.behaviour_mut(), &topic_name);
+                            if unsubscribed {
+                                subscribed_topics.remove(&topic_name);
+                            }
+                            let _ = reply.send(unsubscribed);
+                        }
+                        Some(Command::SubscribeTopics(topic_names, reply)) => {
+                            let results = topic_names
+                                .into_iter()
+                                .map(|topic_name| {
+                                    desired_subscriptions.insert(topic_name.clone());
+                                    topic_name_by_hash.insert(topic_scheme.hash(&topic_name), topic_name.clone());
+                                    let ok = topic_scheme.subscribe(swarm.behaviour_mut(), &topic_name).unwrap_or(false);
+                                    if ok {
+                                        subscribed_topics.insert(topic_name.clone());
+                                    }
+                                    (topic_name, ok)
+                                })
+                                .collect();
+                            let _ = reply.send(results);
+                        }
+                        Some(Command::UnsubscribeTopics(topic_names, reply)) => {
+                            let results = topic_names
+                                .into_iter()
+                                .map(|topic_name| {
+                                    desired_subscriptions.remove(&topic_name);
+                                    let ok = topic_scheme.unsubscribe(swarm.behaviour_mut(), &topic_name);
+                                    if ok {
+                                        subscribed_topics.remove(&topic_name);
+                                    }
+                                    (topic_name, ok)
+                                })
+                                .collect();
+                            let _ = reply.send(results);
+                        }
+                        Some(Command::GetSubscriptions(reply)) => {
+                            let _ = reply.send(subscribed_topics.iter().cloned().collect());
+                        }
+                        Some(Command::GetDesiredSubscriptions(reply)) => {
+                            let _ = reply.send(desired_subscriptions.iter().cloned().collect());
+                        }
+                        Some(Command::PublishGossip(topic, data, reply)) => {
+                            let mut blocked_wait = None;
+                            let mut rate_limited = false;
+                            if let Some(limiter) = publish_rate_limits.get_mut(&topic) {
+                                if !limiter.try_consume() {
+                                    if limiter.block {
+                                        blocked_wait = Some(limiter.wait_duration());
+                                    } else {
+                                        rate_limited = true;
+                                    }
+                                }
+                            }
+                            if rate_limited {
+                                let _ = reply.send(Err("PublishRateLimited".to_string()));
+                            } else {
+                                if let Some(wait) = blocked_wait {
+                                    tokio::time::sleep(wait).await;
+                                    if let Some(limiter) = publish_rate_limits.get_mut(&topic) {
+                                        limiter.try_consume();
+                                    }
+                                }
+                                let data = if replay_window.is_some() { encode_replay_envelope(data) } else { data };
+                                let result = match encode_gossip_payload(gossip_signing_key.as_ref(), data) {
+                                    Ok(payload) => {
+                                        let topic_hash = topic_scheme.hash(&topic);
+                                        topic_name_by_hash.insert(topic_hash.clone(), topic.clone());
+                                        let result =
+                                            swarm.behaviour_mut().gossipsub.publish(topic_hash, payload).map(|_| ()).map_err(|e| match e {
+                                                gossipsub::PublishError::NoPeersSubscribedToTopic => "InsufficientPeers".to_string(),
+                                                gossipsub::PublishError::MessageTooLarge => {
+                                                    format!("MessageTooLarge: limit is {gossipsub_max_transmit_size} bytes")
+                                                }
+                                                other => other.to_string(),
+                                            });
+                                        if result.is_ok() {
+                                            gossip_stats.entry(topic).or_default().published += 1;
+                                        }
+                                        result
+                                    }
+                                    Err(e) => Err(e.to_string()),
+                                };
+                                let _ = reply.send(result);
+                            }
+                        }
+                        Some(Command::PublishGossipWithId(topic, data, id, reply)) => {
+                            let mut blocked_wait = None;
+                            let mut rate_limited = false;
+                            if let Some(limiter) = publish_rate_limits.get_mut(&topic) {
+                                if !limiter.try_consume() {
+                                    if limiter.block {
+                                        blocked_wait = Some(limiter.wait_duration());
+                                    } else {
+                                        rate_limited = true;
+                                    }
+                                }
+                            }
+                            if rate_limited {
+                                let _ = reply.send(Err("PublishRateLimited".to_string()));
+                            } else {
+                                if let Some(wait) = blocked_wait {
+                                    tokio::time::sleep(wait).await;
+                                    if let Some(limiter) = publish_rate_limits.get_mut(&topic) {
+                                        limiter.try_consume();
+                                    }
+                                }
+                                let payload = encode_gossip_payload_with_id(&id, data);
+                                let topic_hash = topic_scheme.hash(&topic);
+                                topic_name_by_hash.insert(topic_hash.clone(), topic);
+                                let result = swarm.behaviour_mut().gossipsub.publish(topic_hash, payload).map(|_| ()).map_err(|e| match e {
+                                    gossipsub::PublishError::NoPeersSubscribedToTopic => "InsufficientPeers".to_string(),
+                                    gossipsub::PublishError::MessageTooLarge => {
+                                        format!("MessageTooLarge: limit is {gossipsub_max_transmit_size} bytes")
+                                    }
+                                    other => other.to_string(),
+                                });
+                                let _ = reply.send(result);
+                            }
+                        }
+                        Some(Command::PublishToTopics(topics, data, reply)) => {
+                            let mut results = Vec::with_capacity(topics.len());
+                            let data = if replay_window.is_some() { encode_replay_envelope(data) } else { data };
+                            match encode_gossip_payload(gossip_signing_key.as_ref(), data) {
+                                Ok(payload) => {
+                                    for topic in topics {
+                                        let mut blocked_wait = None;
+                                        let mut rate_limited = false;
+                                        if let Some(limiter) = publish_rate_limits.get_mut(&topic) {
+                                            if !limiter.try_consume() {
+                                                if limiter.block {
+                                                    blocked_wait = Some(limiter.wait_duration());
+                                                } else {
+                                                    rate_limited = true;
+                                                }
+                                            }
+                                        }
+                                        if rate_limited {
+                                            results.push((topic, None));
+                                            continue;
+                                        }
+                                        if let Some(wait) = blocked_wait {
+                                            tokio::time::sleep(wait).await;
+                                            if let Some(limiter) = publish_rate_limits.get_mut(&topic) {
+                                                limiter.try_consume();
+                                            }
+                                        }
+                                        // Any publish failure (no subscribers, message too large, ...) is
+                                        // collapsed to `None` here rather than surfaced per-topic, so a
+                                        // batch publish never aborts partway through on one bad topic.
+                                        let topic_hash = topic_scheme.hash(&topic);
+                                        topic_name_by_hash.insert(topic_hash.clone(), topic.clone());
+                                        let message_id =
+                                            swarm.behaviour_mut().gossipsub.publish(topic_hash, payload.clone()).ok();
+                                        results.push((topic, message_id.map(|id| id.to_string())));
+                                    }
+                                }
+                                Err(_) => results.extend(topics.into_iter().map(|topic| (topic, None))),
+                            }
+                            let _ = reply.send(results);
+                        }
+                        Some(Command::HasMeshPeers(topic, reply)) => {
+                            let topic_hash = topic_scheme.hash(&topic);
+                            let has_peers = swarm.behaviour_mut().gossipsub.mesh_peers(&topic_hash).next().is_some();
+                            let _ = reply.send(has_peers);
+                        }
+                        Some(Command::GetGossipsubAllPeers(reply)) => {
+                            let peers = swarm
+                                .behaviour_mut()
+                                .gossipsub
+                                .all_peers()
+                                .map(|(peer_id, topics)| {
+                                    (peer_id.to_string(), topics.into_iter().map(|t| t.to_string()).collect())
+                                })
+                                .collect();
+                            let _ = reply.send(peers);
+                        }
+                        Some(Command::GetGossipsubAllMeshPeers(reply)) => {
+                            let peers = swarm.behaviour_mut().gossipsub.all_mesh_peers().map(|p| p.to_string()).collect();
+                            let _ = reply.send(peers);
+                        }
+                        Some(Command::GetTopicHealth(topic, reply)) => {
+                            let topic_hash = topic_scheme.hash(&topic);
+                            let mesh_size = swarm.behaviour_mut().gossipsub.mesh_peers(&topic_hash).count();
+                            // `behaviour::build` never overrides these, so the defaults
+                            // `gossipsub::Config` itself ships with are exactly what's live.
+                            let config = gossipsub::Config::default();
+                            let (mesh_n_low, mesh_n_high) = (config.mesh_n_low(), config.mesh_n_high());
+                            let status = if mesh_size < mesh_n_low {
+                                "under_provisioned"
+                            } else if mesh_size > mesh_n_high {
+                                "over_provisioned"
+                            } else {
+                                "healthy"
+                            };
+                            let _ = reply.send(TopicHealth { mesh_size, mesh_n_low, mesh_n_high, fanout_size: 0, status });
+                        }
+                        Some(Command::SetPersistentPeers(peers)) => {
+                            persistent_peers = peers
+                                .into_iter()
+                                .map(|(peer_id, addr)| (peer_id, PersistentPeer { addr, attempt: 0 }))
+                                .collect();
+                        }
+                        Some(Command::SetKademliaMode(mode)) => {
+                            swarm.behaviour_mut().kad.set_mode(mode);
+                        }
+                        Some(Command::BootstrapWith(peers, reply)) => {
+                            for (peer_id, addr) in peers {
+                                swarm.behaviour_mut().kad.add_address(&peer_id, addr);
+                            }
+                            let result = swarm.behaviour_mut().kad.bootstrap().map(|_| ()).map_err(|e| e.to_string());
+                            let _ = reply.send(result);
+                        }
+                        Some(Command::RemoveListener(listener_id, reply)) => {
+                            let _ = reply.send(swarm.remove_listener(listener_id));
+                        }
+                        Some(Command::AddExternalAddress(addr)) => {
+                            swarm.add_external_address(addr);
+                        }
+                        Some(Command::RemoveExternalAddress(addr)) => {
+                            swarm.remove_external_address(&addr);
+                        }
+                        Some(Command::GetExternalAddresses(reply)) => {
+                            let addrs =
+                                swarm.external_addresses().filter(|a| address_filter.allows(a)).map(|a| a.to_string()).collect();
+                            let _ = reply.send(addrs);
+                        }
+                        Some(Command::GetListenAddrs(reply)) => {
+                            let addrs: Vec<Multiaddr> = swarm.listeners().cloned().collect();
+                            let addrs = if interface_expansion { expand_wildcard_addrs(addrs) } else { addrs };
+                            let addrs: Vec<Multiaddr> = addrs.into_iter().filter(|a| address_filter.allows(a)).collect();
+                            let _ = reply.send(addrs);
+                        }
+                        Some(Command::SetInterfaceExpansion(enabled)) => {
+                            interface_expansion = enabled;
+                        }
+                        Some(Command::SetAddressFilter(mode)) => {
+                            address_filter = mode;
+                            // Reconciles identify's advertised set (and, transitively, what peers
+                            // feed into their own Kademlia tables) with the new mode immediately,
+                            // rather than waiting for the next `NewListenAddr`.
+                            let listen_addrs: Vec<Multiaddr> = swarm.listeners().cloned().collect();
+                            for addr in listen_addrs {
+                                if address_filter.allows(&addr) {
+                                    swarm.add_external_address(addr);
+                                } else {
+                                    swarm.remove_external_address(&addr);
+                                }
+                            }
+                        }
+                        Some(Command::SetMinAgentVersion(req)) => {
+                            min_agent_version = req;
+                        }
+                        Some(Command::SetReplayWindow(window)) => {
+                            replay_window = window;
+                            if window.is_none() {
+                                seen_replay_nonces.clear();
+                            }
+                        }
+                        Some(Command::GetLiveGossipStats(reply)) => {
+                            let stats = gossip_stats
+                                .iter()
+                                .map(|(topic, s)| {
+                                    let mut counters = HashMap::new();
+                                    counters.insert("published".to_string(), s.published);
+                                    counters.insert("received".to_string(), s.received);
+                                    counters.insert("duplicates_rejected".to_string(), s.duplicates_rejected);
+                                    counters.insert("validation_failures".to_string(), s.validation_failures);
+                                    (topic.clone(), counters)
+                                })
+                                .collect();
+                            let _ = reply.send(stats);
+                        }
+                        Some(Command::SetRecordValidator(callback)) => {
+                            record_validator = callback;
+                        }
+                        Some(Command::SetGossipSigningKey(keypair)) => {
+                            gossip_signing_key = keypair;
+                        }
+                        Some(Command::SetRrSigningKey(keypair)) => {
+                            rr_signing_key = keypair;
+                        }
+                        Some(Command::SetRequireRrSignature(required)) => {
+                            require_rr_signature = required;
+                        }
+                        Some(Command::SetInboundRateLimit(limit)) => {
+                            rate_limiter = limit.map(|(per_ip_per_sec, burst)| InboundRateLimiter::new(per_ip_per_sec, burst));
+                        }
+                        Some(Command::Redial(peer_id)) => {
+                            if let Some(peer) = persistent_peers.get(&peer_id) {
+                                let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer_id)
+                                    .addresses(vec![peer.addr.clone()])
+                                    .build();
+                                dial_started.insert(opts.connection_id(), Instant::now());
+                                pending_dials.insert(opts.connection_id(), peer.addr.clone());
+                                let _ = swarm.dial(opts);
+                            }
+                        }
+                        Some(Command::GetLastDialDuration(peer_id, reply)) => {
+                            let _ = reply.send(last_dial_durations.get(&peer_id).copied());
+                        }
+                        Some(Command::GetAverageDialDuration(reply)) => {
+                            let avg = if dial_duration_count == 0 {
+                                None
+                            } else {
+                                Some(dial_duration_total / dial_duration_count as f64)
+                            };
+                            let _ = reply.send(avg);
+                        }
+                        Some(Command::GetPeerHealth(peer_id, reply)) => {
+                            let connection_count =
+                                connections.lock().unwrap().values().filter(|r| r.peer_id == peer_id).count();
+                            let _ = reply.send(peer_health_score(peer_stats.get(&peer_id), connection_count));
+                        }
+                        Some(Command::BanPeer(peer_id)) => {
+                            banned_peers.insert(peer_id);
+                            allowed_peers.remove(&peer_id);
+                            for (connection_id, record) in connections.lock().unwrap().iter() {
+                                if record.peer_id == peer_id {
+                                    swarm.close_connection(*connection_id);
+                                }
+                            }
+                        }
+                        Some(Command::UnbanPeer(peer_id)) => {
+                            banned_peers.remove(&peer_id);
+                        }
+                        Some(Command::AllowPeer(peer_id)) => {
+                            allowed_peers.insert(peer_id);
+                        }
+                        Some(Command::DisallowPeer(peer_id)) => {
+                            allowed_peers.remove(&peer_id);
+                            if !allowed_peers.is_empty() {
+                                for (connection_id, record) in connections.lock().unwrap().iter() {
+                                    if record.peer_id == peer_id {
+                                        swarm.close_connection(*connection_id);
+                                    }
+                                }
+                            }
+                        }
+                        Some(Command::ExportAccessLists(reply)) => {
+                            let banned = banned_peers.iter().map(|p| p.to_string()).collect();
+                            let allowed = allowed_peers.iter().map(|p| p.to_string()).collect();
+                            let _ = reply.send((banned, allowed));
+                        }
+                        Some(Command::ImportAccessLists(banned, allowed)) => {
+                            banned_peers.extend(banned);
+                            allowed_peers.extend(allowed);
+                        }
+                        Some(Command::BlacklistGossipPeer(peer_id)) => {
+                            swarm.behaviour_mut().gossipsub.blacklist_peer(&peer_id);
+                        }
+                        Some(Command::RemoveBlacklistedGossipPeer(peer_id)) => {
+                            swarm.behaviour_mut().gossipsub.remove_blacklisted_peer(&peer_id);
+                        }
+                        Some(Command::SetGossipCacheCapacity(capacity)) => {
+                            gossip_cache_capacity = capacity;
+                            while gossip_cache.len() > gossip_cache_capacity {
+                                gossip_cache.pop_front();
+                            }
+                        }
+                        Some(Command::GetCachedGossipMessages(reply)) => {
+                            let _ = reply.send(gossip_cache.iter().cloned().collect());
+                        }
+                        Some(Command::GetGossipCacheBytes(reply)) => {
+                            let _ = reply.send(gossip_cache.iter().map(|(topic, data)| topic.len() + data.len()).sum());
+                        }
+                        Some(Command::SetKeepAlivePeer(peer_id, enabled)) => {
+                            if enabled {
+                                keep_alive_peers.insert(peer_id);
+                            } else {
+                                keep_alive_peers.remove(&peer_id);
+                                last_keepalive_sent.remove(&peer_id);
+                            }
+                        }
+                        Some(Command::GetKeepAlivePeers(reply)) => {
+                            let _ = reply.send(keep_alive_peers.iter().map(|p| p.to_string()).collect());
+                        }
+                        Some(Command::SendRrRequest(peer_id, protocol, data, timeout, reply)) => {
+                            let payload = transfer::encode_rr_request(&transfer::RrRequest { protocol, data });
+                            let payload = encode_rr_payload(rr_signing_key.as_ref(), payload)
+                                .expect("rr payload signing is infallible");
+                            let request_id = swarm.behaviour_mut().rr.send_request(&peer_id, payload);
+                            pending_rr_requests.insert(
+                                request_id,
+                                PendingRrRequest { deadline: Instant::now() + timeout, reply: Some(reply) },
+                            );
+                        }
+                        Some(Command::RespondRrRequest(request_id, data, reply)) => {
+                            let result = match pending_inbound_requests.remove(&request_id) {
+                                Some(channel) => swarm
+                                    .behaviour_mut()
+                                    .rr
+                                    .send_response(channel, data)
+                                    .map_err(|_| "peer disconnected before the response was sent".to_string()),
+                                None => Err("unknown or already-answered request_id".to_string()),
+                            };
+                            let _ = reply.send(result);
+                        }
+                        Some(Command::SetPublishRateLimit(topic, limit)) => {
+                            match limit {
+                                Some((messages_per_sec, block)) => {
+                                    publish_rate_limits.insert(topic, PublishRateLimit::new(messages_per_sec, block));
+                                }
+                                None => {
+                                    publish_rate_limits.remove(&topic);
+                                }
+                            }
+                        }
+                        Some(Command::TriggerAutonatProbe(addr)) => {
+                            pending_autonat_probes.push_back(addr.clone());
+                            swarm.behaviour_mut().autonat.probe_address(addr);
+                        }
+                        Some(Command::GetReachabilityConfidence(reply)) => {
+                            let _ = reply.send(swarm.behaviour().autonat.confidence());
+                        }
+                        Some(Command::IsAddressReachable(addr, reply)) => {
+                            let _ = reply.send(autonat_reachability.get(&addr).copied());
+                        }
+                        Some(Command::SetPeerTtl(ttl)) => {
+                            peer_ttl = ttl;
+                        }
+                        Some(Command::PruneExpiredPeers(reply)) => {
+                            let pruned = match peer_ttl {
+                                Some(ttl) => prune_expired_mdns_peers(
+                                    &mut mdns_last_seen,
+                                    &mut address_book,
+                                    &mut mdns_addresses,
+                                    &mut discovery_methods,
+                                    ttl,
+                                ),
+                                None => 0,
+                            };
+                            let _ = reply.send(pruned);
+                        }
+                        Some(Command::GetTopicSubscribers(topic, reply)) => {
+                            let topic_hash = topic_scheme.hash(&topic);
+                            let subscribers = topic_subscribers
+                                .get(&topic_hash)
+                                .map(|peers| peers.iter().map(|p| p.to_string()).collect())
+                                .unwrap_or_default();
+                            let _ = reply.send(subscribers);
+                        }
+                        Some(Command::GetRoutingUpdateCount(reply)) => {
+                            let _ = reply.send(routing_updates);
+                        }
+                        Some(Command::GetDhtStoreStats(reply)) => {
+                            let store = swarm.behaviour_mut().kad.store_mut();
+                            let _ = reply.send(DhtStoreStats {
+                                record_count: store.records().count(),
+                                max_records: dht_store_config.max_records,
+                                provided_key_count: store.provided().count(),
+                                max_provided_keys: dht_store_config.max_provided_keys,
+                                max_value_bytes: dht_store_config.max_value_bytes,
+                            });
+                        }
+                        Some(Command::StartProviding(key, reply)) => {
+                            let result = swarm.behaviour_mut().kad.start_providing(key).map(|_| ()).map_err(|e| e.to_string());
+                            let _ = reply.send(result);
+                        }
+                        Some(Command::StopProviding(key)) => {
+                            swarm.behaviour_mut().kad.stop_providing(&key);
+                        }
+                        Some(Command::GetProvidedKeys(reply)) => {
+                            let keys = swarm.behaviour_mut().kad.store_mut().provided().map(|p| p.key.to_vec()).collect();
+                            let _ = reply.send(keys);
+                        }
+                        Some(Command::GetRoutingTableSize(reply)) => {
+                            let size: usize =
+                                swarm.behaviour_mut().kad.kbuckets().map(|bucket| bucket.num_entries()).sum();
+                            let _ = reply.send(size);
+                        }
+                        Some(Command::GetKBuckets(reply)) => {
+                            let buckets = swarm
+                                .behaviour_mut()
+                                .kad
+                                .kbuckets()
+                                .map(|bucket| {
+                                    let distance = bucket.range().0.ilog2().unwrap_or(0);
+                                    let peers =
+                                        bucket.iter().map(|entry| entry.node.key.preimage().to_string()).collect();
+                                    (distance, peers)
+                                })
+                                .collect();
+                            let _ = reply.send(buckets);
+                        }
+                        Some(Command::GetClosestLocalPeers(key, count, reply)) => {
+                            let target = kad::KBucketKey::from(key);
+                            let mut peers: Vec<(kad::KBucketDistance, PeerId)> = swarm
+                                .behaviour_mut()
+                                .kad
+                                .kbuckets()
+                                .flat_map(|bucket| {
+                                    bucket
+                                        .iter()
+                                        .map(|entry| (target.distance(entry.node.key), *entry.node.key.preimage()))
+                                        .collect::<Vec<_>>()
+                                })
+                                .collect();
+                            peers.sort_by_key(|(distance, _)| *distance);
+                            peers.truncate(count);
+                            let _ = reply.send(peers.into_iter().map(|(_, peer_id)| peer_id.to_string()).collect());
+                        }
+                        Some(Command::SendFile(peer_id, protocol, path, reply)) => {
+                            let result = (|| -> Result<u64, String> {
+                                let mut file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+                                let len = file.metadata().map_err(|e| e.to_string())?.len();
+                                let total = len.div_ceil(transfer::CHUNK_SIZE as u64).max(1) as u32;
+                                let mut data = vec![0u8; transfer::CHUNK_SIZE];
+                                let read = std::io::Read::read(&mut file, &mut data).map_err(|e| e.to_string())?;
+                                data.truncate(read);
+                                next_transfer_id += 1;
+                                let transfer_id = next_transfer_id;
+                                let chunk = FileChunk { transfer_id, protocol: protocol.clone(), seq: 1, total, data: data.clone() };
+                                let payload = encode_rr_payload(rr_signing_key.as_ref(), transfer::encode_chunk(&chunk))
+                                    .map_err(|e| e.to_string())?;
+                                let request_id = swarm.behaviour_mut().rr.send_request(&peer_id, payload);
+                                outbound_transfers.insert(
+                                    request_id,
+                                    OutboundTransfer {
+                                        transfer_id,
+                                        protocol,
+                                        peer_id,
+                                        file,
+                                        seq: 1,
+                                        total,
+                                        chunk_data: data,
+                                        retries_used: 0,
+                                    },
+                                );
+                                Ok(transfer_id)
+                            })();
+                            let _ = reply.send(result);
+                        }
+                        Some(Command::Shutdown) => break,
+                        None => break,
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn translate_inbound_probe(event: autonat::InboundProbeEvent) -> Option<NodeEvent> {
+    match event {
+        autonat::InboundProbeEvent::Request { peer, addresses, .. } => Some(NodeEvent::AutonatProbe {
+            direction: "inbound",
+            peer_id: Some(peer.to_string()),
+            address: addresses.first().map(|a| a.to_string()),
+            outcome: "requested",
+            error: None,
+        }),
+        autonat::InboundProbeEvent::Response { peer, address, .. } => Some(NodeEvent::AutonatProbe {
+            direction: "inbound",
+            peer_id: Some(peer.to_string()),
+            address: Some(address.to_string()),
+            outcome: "succeeded",
+            error: None,
+        }),
+        autonat::InboundProbeEvent::Error { peer, error, .. } => Some(NodeEvent::AutonatProbe {
+            direction: "inbound",
+            peer_id: Some(peer.to_string()),
+            address: None,
+            outcome: "failed",
+            error: Some(format!("{error:?}")),
+        }),
+    }
+}
+
+fn translate_outbound_probe(event: autonat::OutboundProbeEvent) -> Option<NodeEvent> {
+    match event {
+        autonat::OutboundProbeEvent::Request { peer, .. } => Some(NodeEvent::AutonatProbe {
+            direction: "outbound",
+            peer_id: Some(peer.to_string()),
+            address: None,
+            outcome: "requested",
+            error: None,
+        }),
+        autonat::OutboundProbeEvent::Response { peer, address, .. } => Some(NodeEvent::AutonatProbe {
+            direction: "outbound",
+            peer_id: Some(peer.to_string()),
+            address: Some(address.to_string()),
+            outcome: "succeeded",
+            error: None,
+        }),
+        autonat::OutboundProbeEvent::Error { peer, error, .. } => Some(NodeEvent::AutonatProbe {
+            direction: "outbound",
+            peer_id: peer.map(|p| p.to_string()),
+            address: None,
+            outcome: "failed",
+            error: Some(format!("{error:?}")),
+        }),
+    }
+}
+
+fn translate(event: SwarmEvent<BehaviourEvent>) -> Option<NodeEvent> {
+    match event {
+        SwarmEvent::ConnectionEstablished { peer_id, num_established, endpoint, .. } => {
+            Some(NodeEvent::ConnectionEstablished {
+                peer_id: peer_id.to_string(),
+                num_established: num_established.get(),
+                direction: if endpoint.is_dialer() { "outbound" } else { "inbound" },
+            })
+        }
+        SwarmEvent::ConnectionClosed { peer_id, num_established, cause, .. } => {
+            if is_stream_limit_error(&cause) {
+                Some(NodeEvent::StreamLimitReached { peer_id: peer_id.to_string() })
+            } else {
+                Some(NodeEvent::ConnectionClosed {
+                    peer_id: peer_id.to_string(),
+                    reason: connection_close_reason(cause),
+                    remaining_connections: num_established,
+                })
+            }
+        }
+        SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+            Some(NodeEvent::OutgoingConnectionError {
+                peer_id: peer_id.map(|p| p.to_string()),
+                error: error.to_string(),
+            })
+        }
+        SwarmEvent::ListenerClosed { listener_id, addresses, reason } => {
+            Some(NodeEvent::ListenerClosed {
+                listener_id: format!("{listener_id:?}"),
+                addresses: addresses.iter().map(|a| a.to_string()).collect(),
+                reason: reason.err().map(|e| e.to_string()),
+            })
+        }
+        SwarmEvent::ListenerError { listener_id, error } => {
+            Some(NodeEvent::ListenerError {
+                listener_id: format!("{listener_id:?}"),
+                error: error.to_string(),
+            })
+        }
+        SwarmEvent::ExternalAddrConfirmed { address } => {
+            Some(NodeEvent::ExternalAddrConfirmed { address: address.to_string() })
+        }
+        SwarmEvent::ExternalAddrExpired { address } => {
+            Some(NodeEvent::ExternalAddrExpired { address: address.to_string() })
+        }
+        _ => None,
+    }
+}
+
+#[pymethods]
+impl Node {
+    #[new]
+    #[pyo3(signature = (
+        keypair=None,
+        network_name=None,
+        kademlia_protocol_name=None,
+        idle_connection_timeout_secs=60,
+        ping_interval_secs=15,
+        gossipsub_max_transmit_size=65536,
+        gossipsub_flood_publish=true,
+        gossipsub_topic_scheme="ident",
+        gossipsub_gossip_lazy=None,
+        gossipsub_gossip_factor=None,
+        gossipsub_history_length=None,
+        gossipsub_history_gossip=None,
+        rr_request_timeout_secs=10,
+        rr_max_retries=2,
+        dht_replication_factor=None,
+        dht_query_timeout_secs=None,
+        dht_parallelism=None,
+        dht_record_ttl_secs=None,
+        dht_provider_record_ttl_secs=None,
+        wss_cert_der=None,
+        wss_key_der=None,
+        noise_prologue=None,
+        dht_max_records=None,
+        dht_max_value_bytes=None,
+        dht_max_provided_keys=None,
+        yamux_receive_window_size=None,
+        yamux_max_buffer_size=None,
+        yamux_max_num_streams=None,
+        autonat_use_connected=true,
+        heartbeat_interval_secs=0,
+        runtime_worker_threads=None,
+        enable_uds=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        keypair: Option<&KeypairManager>,
+        network_name: Option<&str>,
+        kademlia_protocol_name: Option<&str>,
+        idle_connection_timeout_secs: u64,
+        ping_interval_secs: u64,
+        gossipsub_max_transmit_size: usize,
+        gossipsub_flood_publish: bool,
+        gossipsub_topic_scheme: &str,
+        gossipsub_gossip_lazy: Option<usize>,
+        gossipsub_gossip_factor: Option<f64>,
+        gossipsub_history_length: Option<usize>,
+        gossipsub_history_gossip: Option<usize>,
+        rr_request_timeout_secs: u64,
+        rr_max_retries: u32,
+        dht_replication_factor: Option<usize>,
+        dht_query_timeout_secs: Option<u64>,
+        dht_parallelism: Option<usize>,
+        dht_record_ttl_secs: Option<u64>,
+        dht_provider_record_ttl_secs: Option<u64>,
+        wss_cert_der: Option<Vec<u8>>,
+        wss_key_der: Option<Vec<u8>>,
+        noise_prologue: Option<Vec<u8>>,
+        dht_max_records: Option<usize>,
+        dht_max_value_bytes: Option<usize>,
+        dht_max_provided_keys: Option<usize>,
+        yamux_receive_window_size: Option<u32>,
+        yamux_max_buffer_size: Option<usize>,
+        yamux_max_num_streams: Option<usize>,
+        autonat_use_connected: bool,
+        heartbeat_interval_secs: u64,
+        runtime_worker_threads: Option<usize>,
+        enable_uds: bool,
+    ) -> PyResult<Self> {
+        if idle_connection_timeout_secs == 0 {
+            return Err(P2pError::Other("idle_connection_timeout_secs must be non-zero".to_string()).into());
+        }
+        if ping_interval_secs == 0 {
+            return Err(P2pError::Other("ping_interval_secs must be non-zero".to_string()).into());
+        }
+        if gossipsub_max_transmit_size == 0 {
+            return Err(P2pError::Other("gossipsub_max_transmit_size must be non-zero".to_string()).into());
+        }
+        if rr_request_timeout_secs == 0 {
+            return Err(P2pError::Other("rr_request_timeout_secs must be non-zero".to_string()).into());
+        }
+        // `network_name` derives a coherent protocol family in one shot, instead of setting
+        // the identify, kademlia, and gossipsub protocol strings independently and risking a
+        // mismatch between them. An explicit `kademlia_protocol_name` still wins over it, so
+        // callers that only need to override kademlia don't have to also restate the others.
+        let identify_protocol_version =
+            network_name.map(|name| format!("/{name}/id/1.0.0")).unwrap_or_else(|| behaviour::IDENTIFY_PROTOCOL_VERSION.to_string());
+        let default_kad_protocol_name = network_name.map(|name| format!("/{name}/kad/1.0.0"));
+        let topic_namespace = network_name.map(|name| name.to_string());
+        let topic_scheme = TopicScheme::parse(gossipsub_topic_scheme, topic_namespace)?;
+        let wss_tls_config = build_wss_tls_config(wss_cert_der, wss_key_der)?;
+        let yamux_config = build_yamux_config(yamux_receive_window_size, yamux_max_buffer_size, yamux_max_num_streams);
+        let mut dht_store_config = kad::store::MemoryStoreConfig::default();
+        if let Some(max_records) = dht_max_records {
+            dht_store_config.max_records = max_records;
+        }
+        if let Some(max_value_bytes) = dht_max_value_bytes {
+            dht_store_config.max_value_bytes = max_value_bytes;
+        }
+        if let Some(max_provided_keys) = dht_max_provided_keys {
+            dht_store_config.max_provided_keys = max_provided_keys;
+        }
+
+        let keypair = match keypair {
+            Some(k) => k.keypair.clone(),
+            None => libp2p::identity::Keypair::generate_ed25519(),
+        };
+        let local_peer_id = PeerId::from(keypair.public());
+        let kad_protocol_name = kademlia_protocol_name
+            .or(default_kad_protocol_name.as_deref())
+            .unwrap_or(behaviour::DEFAULT_KAD_PROTOCOL);
+
+        let gossip_tuning = behaviour::GossipTuning {
+            gossip_lazy: gossipsub_gossip_lazy,
+            gossip_factor: gossipsub_gossip_factor,
+            history_length: gossipsub_history_length,
+            history_gossip: gossipsub_history_gossip,
+        };
+        let ttl_from_secs = |secs: u64| if secs == 0 { None } else { Some(Duration::from_secs(secs)) };
+        let kad_tuning = behaviour::KadTuning {
+            replication_factor: dht_replication_factor
+                .map(|n| std::num::NonZeroUsize::new(n).ok_or(()))
+                .transpose()
+                .map_err(|_| P2pError::Other("dht_replication_factor must be non-zero".to_string()))?,
+            query_timeout: dht_query_timeout_secs.map(Duration::from_secs),
+            parallelism: dht_parallelism
+                .map(|n| std::num::NonZeroUsize::new(n).ok_or(()))
+                .transpose()
+                .map_err(|_| P2pError::Other("dht_parallelism must be non-zero".to_string()))?,
+            record_ttl: dht_record_ttl_secs.map(ttl_from_secs),
+            provider_record_ttl: dht_provider_record_ttl_secs.map(ttl_from_secs),
+        };
+        let behaviour = behaviour::build(
+            &keypair,
+            kad_protocol_name,
+            &identify_protocol_version,
+            Duration::from_secs(ping_interval_secs),
+            gossipsub_max_transmit_size,
+            gossipsub_flood_publish,
+            gossip_tuning,
+            kad_tuning,
+            dht_store_config.clone(),
+            autonat_use_connected,
+            Duration::from_secs(rr_request_timeout_secs),
+        )
+        .map_err(P2pError::Transport)?;
+        let supported_protocols = vec![
+            kad_protocol_name.to_string(),
+            identify_protocol_version,
+            behaviour::RR_PROTOCOL.to_string(),
+        ];
+
+        let yamux_config_for_ws = yamux_config.clone();
+        let noise_prologue_for_ws = noise_prologue.clone();
+        let yamux_config_for_uds = yamux_config.clone();
+        let noise_prologue_for_uds = noise_prologue.clone();
+        let with_tcp_and_ws = libp2p::SwarmBuilder::with_existing_identity(keypair.clone())
+            .with_tokio()
+            .with_tcp(
+                Default::default(),
+                move |keypair: &libp2p::identity::Keypair| build_noise_config(keypair, noise_prologue.clone()),
+                move || yamux_config.clone(),
+            )
+            .map_err(|e| P2pError::Transport(e.to_string()))?
+            .with_other_transport(move |keypair| {
+                let tcp = libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default());
+                let dns = libp2p::dns::tokio::Transport::system(tcp)
+                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+                let mut ws_config = libp2p::websocket::Config::new(dns);
+                ws_config.set_tls_config(wss_tls_config);
+                let noise = build_noise_config(keypair, noise_prologue_for_ws.clone())
+                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+                Ok(ws_config
+                    .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+                    .authenticate(noise)
+                    .multiplex(yamux_config_for_ws))
+            })
+            .map_err(|e| P2pError::Transport(e.to_string()))?;
+
+        // Unix domain sockets are opt-in (see `Node.enable_uds`'s doc comment): a co-located
+        // sidecar process on the same host can dial/listen on `/unix/<path>` without the
+        // overhead of the TCP loopback stack. Wired the same way as the WS transport above —
+        // manually upgraded with noise and yamux before it satisfies `with_other_transport`'s
+        // `Transport<Output = (PeerId, Muxer)>` bound.
+        let swarm = if enable_uds {
+            with_tcp_and_ws
+                .with_other_transport(move |keypair| {
+                    // The tokio provider hands back a raw `tokio::net::UnixStream`, which
+                    // implements `tokio::io::{AsyncRead, AsyncWrite}` but not the `futures`
+                    // versions `.authenticate()` needs (unlike `libp2p-tcp`'s tokio provider,
+                    // which wraps its stream itself); `tokio_util::compat` bridges the two.
+                    use tokio_util::compat::TokioAsyncReadCompatExt;
+                    let uds = libp2p::uds::TokioUdsConfig::new().map(|stream, _| stream.compat());
+                    let noise = build_noise_config(keypair, noise_prologue_for_uds.clone())
+                        .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+                    Ok(uds
+                        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+                        .authenticate(noise)
+                        .multiplex(yamux_config_for_uds))
+                })
+                .map_err(|e| P2pError::Transport(e.to_string()))?
+                .with_dns()
+                .map_err(|e| P2pError::Transport(e.to_string()))?
+                .with_behaviour(|_| behaviour)
+                .map_err(|e| P2pError::Transport(e.to_string()))?
+                .with_swarm_config(|cfg| {
+                    cfg.with_idle_connection_timeout(Duration::from_secs(idle_connection_timeout_secs))
+                })
+                .build()
+        } else {
+            with_tcp_and_ws
+                .with_dns()
+                .map_err(|e| P2pError::Transport(e.to_string()))?
+                .with_behaviour(|_| behaviour)
+                .map_err(|e| P2pError::Transport(e.to_string()))?
+                .with_swarm_config(|cfg| {
+                    cfg.with_idle_connection_timeout(Duration::from_secs(idle_connection_timeout_secs))
+                })
+                .build()
+        };
+
+        let events = Arc::new(Mutex::new(EventQueue::default()));
+        let listeners = Arc::new(Mutex::new(HashMap::new()));
+        let active_listen_addrs = Arc::new(Mutex::new(std::collections::HashSet::new()));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let events_clone = events.clone();
+        let listeners_clone = listeners.clone();
+        let active_listen_addrs_clone = active_listen_addrs.clone();
+        let connections_clone = connections.clone();
+        let tx_clone = tx.clone();
+        let swarm_thread = std::thread::spawn(move || {
+            run_swarm(
+                swarm,
+                events_clone,
+                listeners_clone,
+                active_listen_addrs_clone,
+                connections_clone,
+                gossipsub_max_transmit_size,
+                topic_scheme,
+                dht_store_config,
+                heartbeat_interval_secs,
+                rr_max_retries,
+                runtime_worker_threads,
+                idle_connection_timeout_secs,
+                tx_clone,
+                rx,
+            )
+        });
+
+        Ok(Self {
+            events,
+            commands: tx,
+            local_peer_id,
+            supported_protocols,
+            listeners,
+            active_listen_addrs,
+            connections,
+            keypair,
+            swarm_thread: Mutex::new(Some(swarm_thread)),
+            started_at: Instant::now(),
+            enable_uds,
+        })
+    }
+
+    fn peer_id(&self) -> String {
+        self.local_peer_id.to_string()
+    }
+
+    /// Protocol names this node negotiates: the configured Kademlia protocol, the
+    /// identify protocol version, and the request-response protocol.
+    fn get_supported_protocols(&self) -> Vec<String> {
+        self.supported_protocols.clone()
+    }
+
+    /// Typed alternative to piecing the same picture together from `peer_id`,
+    /// `get_dialable_addresses`, `get_connection_details`, `get_pending_dials`, and `close`'s
+    /// effects: `running` is `false` once `close()` has been called (or `Drop` has run).
+    fn get_network_info_struct(&self) -> PyResult<NetworkInfo> {
+        let num_listeners = self.listeners.lock().unwrap().len();
+        let num_connected = self
+            .connections
+            .lock()
+            .unwrap()
+            .values()
+            .map(|record| record.peer_id)
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+        let num_pending_dials = self.get_pending_dials()?;
+        let running = self.swarm_thread.lock().unwrap().is_some();
+        Ok(NetworkInfo {
+            peer_id: self.local_peer_id.to_string(),
+            num_listeners,
+            num_connected,
+            num_pending_dials,
+            running,
+            uptime_secs: self.started_at.elapsed().as_secs(),
+        })
+    }
+
+    /// Dials `addr`. If `addr` is just a peer id with no transport component (e.g.
+    /// `/p2p/<peerid>`), which libp2p can't dial on its own, this looks up that peer's known
+    /// addresses in the address book (see `get_known_addresses`) and dials those instead, raising `ValueError`
+    /// if none are known.
+    fn dial(&self, addr: String) -> PyResult<()> {
+        let parsed: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+
+        if let Some(peer_id) = peer_id_only(&parsed) {
+            let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+            self.commands
+                .send(Command::GetKnownAddresses(peer_id, reply_tx))
+                .map_err(|e| P2pError::Other(e.to_string()))?;
+            let known = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+            if known.is_empty() {
+                return Err(PyValueError::new_err(format!(
+                    "{addr} has no transport component and no addresses are known for {peer_id}"
+                )));
+            }
+            self.commands
+                .send(Command::DialKnownAddresses(peer_id, known))
+                .map_err(|e| P2pError::Other(e.to_string()))?;
+            return Ok(());
+        }
+
+        self.commands
+            .send(Command::Dial(parsed))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Dials `peer_id` at `addresses`, subject to `condition`: `"disconnected"` only dials if
+    /// there's no established connection to the peer, `"not_dialing"` only dials if there's
+    /// no dial already in flight, and `"always"` dials unconditionally (only subject to
+    /// `set_max_concurrent_dials`). Unlike `dial`, this never falls back to the peer's known
+    /// addresses — `addresses` must be non-empty.
+    fn dial_peer_conditional(&self, peer_id: String, addresses: Vec<String>, condition: &str) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let addrs = addresses
+            .into_iter()
+            .map(|a| a.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}"))))
+            .collect::<Result<Vec<Multiaddr>, _>>()?;
+        if addrs.is_empty() {
+            return Err(PyValueError::new_err("addresses must be non-empty"));
+        }
+        let condition = match condition {
+            "disconnected" => libp2p::swarm::dial_opts::PeerCondition::Disconnected,
+            "not_dialing" => libp2p::swarm::dial_opts::PeerCondition::NotDialing,
+            "always" => libp2p::swarm::dial_opts::PeerCondition::Always,
+            other => return Err(PyValueError::new_err(format!("unknown dial condition: {other}"))),
+        };
+        self.commands
+            .send(Command::DialConditional(peer_id, addrs, condition))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Number of connection attempts currently in flight, i.e. dialed but neither
+    /// established nor failed yet. Useful for detecting a node that's queueing dials
+    /// faster than they resolve.
+    fn get_pending_dials(&self) -> PyResult<usize> {
+        Ok(self.get_pending_dial_addresses()?.len())
+    }
+
+    /// Blocks until at least `min_count` distinct peers are connected, or `timeout_secs`
+    /// elapses, returning whether the target was reached. Releases the GIL while waiting so
+    /// other Python threads keep running. Polls rather than subscribing to events, since the
+    /// driver thread already tracks connections independently of `poll_event`/`get_events`.
+    fn wait_for_peers(&self, py: Python<'_>, min_count: usize, timeout_secs: f64) -> bool {
+        let deadline = Instant::now() + Duration::from_secs_f64(timeout_secs.max(0.0));
+        py.detach(|| loop {
+            let connected = self
+                .connections
+                .lock()
+                .unwrap()
+                .values()
+                .map(|record| record.peer_id)
+                .collect::<std::collections::HashSet<_>>()
+                .len();
+            if connected >= min_count {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        })
+    }
+
+    /// Addresses currently being dialed; see `get_pending_dials` for the count.
+    fn get_pending_dial_addresses(&self) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetPendingDials(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Caps how many dials (`Dial`/`DialKnownAddresses`/`dial`) are in flight at once.
+    /// `None` (the default) starts every dial immediately; `Some(n)` queues the rest, starting
+    /// queued ones as in-flight dials complete, so bootstrapping from a large peer list doesn't
+    /// thunder-herd the local socket table or trip remote rate limits. See
+    /// `get_dial_queue_depth` for how many dials are currently waiting.
+    #[pyo3(signature = (limit=None))]
+    fn set_max_concurrent_dials(&self, limit: Option<usize>) -> PyResult<()> {
+        self.commands.send(Command::SetMaxConcurrentDials(limit)).map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Number of dials queued behind `set_max_concurrent_dials`'s limit, not yet started.
+    fn get_dial_queue_depth(&self) -> PyResult<usize> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetDialQueueDepth(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Seconds between dialing `peer_id` and its most recent `ConnectionEstablished`, or
+    /// `None` if this node has never completed a dial to that peer. Useful for picking
+    /// geographically close bootstrap nodes.
+    fn get_last_dial_duration(&self, peer_id: String) -> PyResult<Option<f64>> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetLastDialDuration(peer_id, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Average dial-establishment latency in seconds, across every dial this node has
+    /// completed so far, or `None` if none have completed yet.
+    fn get_average_dial_duration(&self) -> PyResult<Option<f64>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetAverageDialDuration(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// A `[0, 1]` connection-health score for `peer_id`, combining its most recent ping
+    /// latency, how many connections are currently open to it, and how often it has
+    /// disconnected in the past. A peer this node has never connected to scores a neutral
+    /// 0.5 on the latency component, 0 on the open-connections component, and a perfect 1.0
+    /// on the disconnect-history component.
+    fn get_peer_health(&self, peer_id: String) -> PyResult<f64> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetPeerHealth(peer_id, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Closes any open connections to `peer_id` and refuses new ones until `unban_peer` is
+    /// called, even if `peer_id` is also on the allowlist.
+    fn ban_peer(&self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands.send(Command::BanPeer(peer_id)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Reverses `ban_peer`. Does not by itself reconnect to `peer_id`.
+    fn unban_peer(&self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands.send(Command::UnbanPeer(peer_id)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Adds `peer_id` to the allowlist. The allowlist only takes effect once it holds at
+    /// least one entry: from then on, every new connection to a peer not on it is closed,
+    /// turning this node allowlist-only. Connections already open when the list was
+    /// populated are left in place.
+    fn allow_peer(&self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands.send(Command::AllowPeer(peer_id)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Removes `peer_id` from the allowlist, closing its connections if the allowlist is
+    /// still non-empty afterwards.
+    fn disallow_peer(&self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands.send(Command::DisallowPeer(peer_id)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Stops exchanging gossipsub messages with `peer_id` (no outgoing publishes reach it, no
+    /// incoming messages from it are accepted) without closing its connection, so other
+    /// protocols (rr, kad, ping, ...) keep working. Finer-grained than `ban_peer`, and the
+    /// right tool for cutting off pubsub spam specifically.
+    fn blacklist_gossip_peer(&self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands.send(Command::BlacklistGossipPeer(peer_id)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Reverses `blacklist_gossip_peer`.
+    fn remove_blacklisted_gossip_peer(&self, peer_id: String) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands
+            .send(Command::RemoveBlacklistedGossipPeer(peer_id))
+            .map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Caps how many recent gossip messages `get_cached_gossip_messages` retains, evicting the
+    /// oldest first once the cap is hit. Defaults to 1000. Lower this to bound memory use
+    /// instead of message count; see `get_gossip_cache_bytes` for the current footprint.
+    fn set_gossip_cache_capacity(&self, capacity: usize) -> PyResult<()> {
+        self.commands.send(Command::SetGossipCacheCapacity(capacity)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Recent gossip messages this node has received, oldest first, as `(topic, data)` pairs,
+    /// for replaying some history to a peer that just subscribed. See
+    /// `set_gossip_cache_capacity` for the retention limit.
+    fn get_cached_gossip_messages(&self) -> PyResult<Vec<(String, Vec<u8>)>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetCachedGossipMessages(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Total bytes (topic names plus payloads) currently held in the gossip message cache, so
+    /// callers can bound it by memory instead of by message count.
+    fn get_gossip_cache_bytes(&self) -> PyResult<usize> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetGossipCacheBytes(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Snapshots the ban list and allowlist as `(banned, allowed)` peer id strings, so an
+    /// operator can persist them (e.g. via `PersistentStorage`) and restore them with
+    /// `import_access_lists` after a restart, instead of losing them every reboot.
+    fn export_access_lists(&self) -> PyResult<(Vec<String>, Vec<String>)> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::ExportAccessLists(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Restores a ban list and allowlist previously produced by `export_access_lists`. Each
+    /// entry in both lists is validated as a parseable `PeerId` before anything is applied;
+    /// the whole call is rejected if any entry fails to parse, rather than importing a
+    /// partial, silently-truncated list.
+    fn import_access_lists(&self, banned: Vec<String>, allowed: Vec<String>) -> PyResult<()> {
+        let parse_all = |entries: Vec<String>| -> PyResult<Vec<PeerId>> {
+            entries
+                .into_iter()
+                .map(|entry| entry.parse().map_err(|e| P2pError::Other(format!("invalid peer id {entry}: {e}")).into()))
+                .collect()
+        };
+        let banned = parse_all(banned)?;
+        let allowed = parse_all(allowed)?;
+        self.commands
+            .send(Command::ImportAccessLists(banned, allowed))
+            .map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Stores `value` under `key` in the DHT, also storing it locally and remembering it so
+    /// `enable_record_republish` can re-put it later. Returns as soon as the query is accepted,
+    /// not once it's confirmed on any remote node — `quorum=1` (the common case, and what this
+    /// used to hardcode) can therefore report success even though nothing has actually stored
+    /// it yet. Watch for a `PutRecordResult` event (see `get_events`) to find out how many
+    /// nodes actually confirmed the write. `quorum=0` is treated the same as `1`.
+    fn put_record(&self, key: Vec<u8>, value: Vec<u8>, quorum: usize) -> PyResult<()> {
+        let quorum = match std::num::NonZeroUsize::new(quorum) {
+            Some(n) => kad::Quorum::N(n),
+            None => kad::Quorum::One,
+        };
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::PutRecord(key, value, quorum, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(P2pError::Other)
+            .map_err(Into::into)
+    }
+
+    /// Looks up `key` in the DHT and returns every distinct value found across the replicas
+    /// queried — Kademlia gives no guarantee that all replicas agree, so unlike a typical
+    /// key/value `get` this can legitimately return more than one entry, and callers that care
+    /// about reconciling divergent values need to see all of them rather than an arbitrary one.
+    /// Returns as soon as `quorum` values have been collected or the underlying query finishes
+    /// on its own, whichever comes first; `quorum=1` (the common case) returns after the first
+    /// hit. Blocks (releasing the GIL) until then or `timeout_secs` elapses.
+    fn get_record(&self, py: Python<'_>, key: Vec<u8>, quorum: usize, timeout_secs: f64) -> PyResult<Vec<Vec<u8>>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetRecord(key, quorum, Duration::from_secs_f64(timeout_secs.max(0.0)), reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let mut values = py
+            .detach(|| reply_rx.blocking_recv())
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(P2pError::Other)?;
+        let mut seen = std::collections::HashSet::new();
+        values.retain(|v| seen.insert(v.clone()));
+        Ok(values)
+    }
+
+    /// Stores `value` in the DHT under `key` even if it's larger than the store's
+    /// `max_value_bytes` limit (see `Node.new`'s `dht_max_value_bytes`), by splitting it into
+    /// `large_record_chunk_key`-derived sub-records and a small manifest at `key` itself
+    /// recording how many there are. `get_large_record` reverses this. Like `put_record`, each
+    /// chunk's put returns as soon as its query is accepted, not once quorum nodes confirm it.
+    fn put_large_record(&self, key: Vec<u8>, value: Vec<u8>, quorum: usize) -> PyResult<()> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands.send(Command::GetDhtStoreStats(reply_tx)).map_err(|e| P2pError::Other(e.to_string()))?;
+        let stats = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+        // The store rejects a value whose length is `>= max_value_bytes`, so chunks must stay
+        // strictly under it.
+        let chunk_size = stats.max_value_bytes.saturating_sub(1).max(1);
+        let chunks: Vec<&[u8]> = if value.is_empty() { vec![&[]] } else { value.chunks(chunk_size).collect() };
+        let total_chunks: u32 = chunks
+            .len()
+            .try_into()
+            .map_err(|_| P2pError::Other("value has too many chunks to address".to_string()))?;
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            self.put_record(large_record_chunk_key(&key, index as u32), chunk.to_vec(), quorum)?;
+        }
+        self.put_record(key, encode_large_record_manifest(total_chunks), quorum)
+    }
+
+    /// Fetches and reassembles a value previously stored with `put_large_record`. Each chunk is
+    /// fetched with its own `quorum`/`timeout_secs`, so total time can be up to
+    /// `timeout_secs * (chunk count + 1)`.
+    fn get_large_record(&self, py: Python<'_>, key: Vec<u8>, quorum: usize, timeout_secs: f64) -> PyResult<Vec<u8>> {
+        let manifest = self
+            .get_record(py, key.clone(), quorum, timeout_secs)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| P2pError::Other("no manifest found for key".to_string()))?;
+        let total_chunks = decode_large_record_manifest(&manifest)
+            .ok_or_else(|| P2pError::Other("value at key is not a put_large_record manifest".to_string()))?;
+        let mut value = Vec::new();
+        for index in 0..total_chunks {
+            let chunk = self
+                .get_record(py, large_record_chunk_key(&key, index), quorum, timeout_secs)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| P2pError::Other(format!("missing chunk {index} of {total_chunks}")))?;
+            value.extend_from_slice(&chunk);
+        }
+        Ok(value)
+    }
+
+    /// Announces this node as a provider of `key` on the DHT, without storing `key`'s value
+    /// there — unlike `put_record`, this is for "ask me directly for this content" rather
+    /// than "store this value for anyone to fetch". Local bookkeeping only; nothing waits for
+    /// the announcement to actually reach the network before returning.
+    fn start_providing(&self, key: Vec<u8>) -> PyResult<()> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::StartProviding(kad::RecordKey::new(&key), reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?.map_err(P2pError::Other).map_err(Into::into)
+    }
+
+    /// Revokes a `start_providing` announcement for `key`, e.g. once the corresponding
+    /// content has been deleted locally. Only affects this node's own local advertisement;
+    /// remote nodes that already cached the provider record forget it once it expires there.
+    fn stop_providing(&self, key: Vec<u8>) -> PyResult<()> {
+        self.commands
+            .send(Command::StopProviding(kad::RecordKey::new(&key)))
+            .map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Every key this node is currently advertising as a provider for, per `start_providing`.
+    /// Lets an operator audit what content this node is actually announcing, and confirm a
+    /// `stop_providing` call took effect.
+    fn get_provided_keys(&self) -> PyResult<Vec<Vec<u8>>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetProvidedKeys(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Without this, `put_record` values silently vanish once their record TTL lapses on the
+    /// remote nodes that stored them: nothing in this crate re-puts them. `Some(interval_secs)`
+    /// periodically re-puts every record this node has `put_record`-ed (tracked in a map,
+    /// cleared only by process restart — there's no `remove_record` exposed yet); `None`
+    /// disables it, which is the default.
+    #[pyo3(signature = (interval_secs=None))]
+    fn enable_record_republish(&self, interval_secs: Option<u64>) -> PyResult<()> {
+        self.commands
+            .send(Command::EnableRecordRepublish(interval_secs))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Total number of peers currently held across all Kademlia k-buckets. Useful for
+    /// diagnosing a poorly populated routing table.
+    fn get_routing_table_size(&self) -> PyResult<usize> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetRoutingTableSize(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Total number of `RoutingUpdated` events (k-bucket insertions or address updates)
+    /// observed so far, for tracking DHT routing table churn over time.
+    fn get_routing_update_count(&self) -> PyResult<u64> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetRoutingUpdateCount(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Snapshot of the Kademlia `MemoryStore`'s occupancy against the caps configured via
+    /// `Node.new`'s `dht_max_records`/`dht_max_value_bytes`/`dht_max_provided_keys`.
+    fn get_dht_store_stats(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetDhtStoreStats(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let stats = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("record_count", stats.record_count)?;
+        dict.set_item("max_records", stats.max_records)?;
+        dict.set_item("provided_key_count", stats.provided_key_count)?;
+        dict.set_item("max_provided_keys", stats.max_provided_keys)?;
+        dict.set_item("max_value_bytes", stats.max_value_bytes)?;
+        Ok(dict.into())
+    }
+
+    /// Authoritative per-topic gossip counters maintained directly in the event loop as
+    /// messages are actually published/received, keyed by topic name and then by
+    /// `published`/`received`/`duplicates_rejected`/`validation_failures`.
+    fn get_live_gossip_stats(&self) -> PyResult<HashMap<String, HashMap<String, u64>>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetLiveGossipStats(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Sends the file at `path` to `peer_id` in length-prefixed chunks over the `rr`
+    /// request-response protocol, tagged with an application-level `protocol` string.
+    /// Returns a transfer id identifying the transfer once its first chunk is queued; the
+    /// rest is sent chunk-by-chunk as each previous chunk is acknowledged, so progress
+    /// (and, on the receiving end, the reassembled file) arrives via `FileTransferProgress`
+    /// events rather than blocking here until the whole file lands.
+    fn send_file(&self, peer_id: String, protocol: String, path: String) -> PyResult<u64> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::SendFile(peer_id, protocol, path.into(), reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(|e| P2pError::Other(e).into())
+    }
+
+    /// Every non-empty k-bucket, as `(distance, peer_ids)` pairs where `distance` is the
+    /// base-2 logarithm of the bucket's upper-bound XOR distance from the local peer id.
+    fn get_kbuckets(&self) -> PyResult<Vec<(u32, Vec<String>)>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands.send(Command::GetKBuckets(reply_tx)).map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// The `count` peers from this node's *local* k-buckets closest to `key` under the XOR
+    /// metric, without issuing a network lookup — unlike a full Kademlia `get_closest_peers`
+    /// query, this only ever consults peers already in the routing table, so it returns
+    /// immediately but may miss closer peers this node simply hasn't discovered yet.
+    fn get_closest_local_peers(&self, key: Vec<u8>, count: usize) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetClosestLocalPeers(key, count, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Addresses this node has learned for `peer_id`, aggregated across Kademlia, identify,
+    /// and mDNS — whichever discovered them first or most recently.
+    fn get_known_addresses(&self, peer_id: String) -> PyResult<Vec<String>> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetKnownAddresses(peer_id, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let addrs = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(addrs.iter().map(|a| a.to_string()).collect())
+    }
+
+    /// The full address book: every peer this node has learned any address for, mapped to
+    /// all addresses known for it. See `get_known_addresses` for a single peer.
+    fn get_address_book(&self) -> PyResult<HashMap<String, Vec<String>>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetAddressBook(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Merges what mdns, Kademlia, and identify have each independently learned into one
+    /// per-peer view: deduped addresses (`get_address_book` already unions these across
+    /// sources) plus which source(s) reported the peer, as `(peer_id, addresses,
+    /// discovery_methods)` tuples. This crate has no separate `MdnsManager`/`KademliaManager`
+    /// classes to query, nor a rendezvous protocol wired in at all, so "every source" here
+    /// means `"mdns"`, `"kad"`, and `"identify"`.
+    fn get_discovered_peers(&self) -> PyResult<Vec<DiscoveredPeer>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetDiscoveredPeers(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Sets how long an mDNS-discovered peer may go without being rediscovered before it's
+    /// considered stale. Rediscovery (another `Discovered` event for the same peer) refreshes
+    /// the timer. Pass `None` to disable TTL-based expiry entirely, which is the default.
+    /// With a TTL set, stale peers are pruned automatically on the driver thread's own tick in
+    /// addition to whenever `prune_expired_peers` is called explicitly.
+    #[pyo3(signature = (secs=None))]
+    fn set_peer_ttl(&self, secs: Option<f64>) -> PyResult<()> {
+        if let Some(secs) = secs {
+            if secs <= 0.0 {
+                return Err(P2pError::Other("secs must be positive".to_string()).into());
+            }
+        }
+        self.commands
+            .send(Command::SetPeerTtl(secs.map(Duration::from_secs_f64)))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Drops mDNS-discovered peers not rediscovered within the TTL set by `set_peer_ttl`,
+    /// removing them from the address book. Returns the number of peers dropped. A no-op
+    /// returning `0` if no TTL is currently set.
+    fn prune_expired_peers(&self) -> PyResult<usize> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::PruneExpiredPeers(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Marks (or unmarks) `peer_id` to have its connection kept alive past libp2p's own
+    /// `idle_connection_timeout_secs`, regardless of how much protocol traffic is otherwise
+    /// flowing to it. While marked, the driver thread periodically opens and closes a
+    /// substream to `peer_id` (roughly twice per `idle_connection_timeout_secs`) purely to
+    /// reset the connection's idle timer; this doesn't affect any other protocol's behavior
+    /// towards the peer. Does not by itself establish a connection — pair with `dial`/
+    /// `set_persistent_peers` for that.
+    fn keep_alive_peer(&self, peer_id: String, enabled: bool) -> PyResult<()> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        self.commands
+            .send(Command::SetKeepAlivePeer(peer_id, enabled))
+            .map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Peers currently marked via `keep_alive_peer(peer_id, True)`, connected or not.
+    fn get_keep_alive_peers(&self) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetKeepAlivePeers(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Sends `data` to `peer_id` over the `rr` request-response behaviour and blocks (releasing
+    /// the GIL) until the matching response arrives, `timeout_secs` elapses, or the request
+    /// otherwise fails (e.g. the peer disconnects) — the RPC primitive most callers actually
+    /// want, without juggling request ids and polling `get_events`. On the receiving end this
+    /// surfaces as `NodeEvent::IncomingRequest`, answered with `Node.respond`.
+    ///
+    /// `protocol` is a caller-chosen logical name carried alongside `data`, not a libp2p wire
+    /// protocol: this crate registers only one actual `rr` protocol (`RR_PROTOCOL`), so it's
+    /// tagged onto the request itself for the receiver to dispatch on, the same way
+    /// `send_file`'s chunks carry their own `protocol` field despite sharing that one wire
+    /// protocol too.
+    ///
+    /// A timeout raises the same error type as any other failure here (see `get_record`), not a
+    /// distinct exception — this crate maps every error to `PyRuntimeError`.
+    fn request(&self, py: Python<'_>, peer_id: String, protocol: String, data: Vec<u8>, timeout_secs: f64) -> PyResult<Vec<u8>> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::SendRrRequest(peer_id, protocol, data, Duration::from_secs_f64(timeout_secs.max(0.0)), reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        py.detach(|| reply_rx.blocking_recv())
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(P2pError::Other)
+            .map_err(Into::into)
+    }
+
+    /// Answers a request surfaced via `NodeEvent::IncomingRequest`, sending `data` back as the
+    /// response. Fails if `request_id` is unknown or has already been answered.
+    fn respond(&self, request_id: u64, data: Vec<u8>) -> PyResult<()> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::RespondRrRequest(request_id, data, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(P2pError::Other)
+            .map_err(Into::into)
+    }
+
+    fn listen(&self, addr: String) -> PyResult<()> {
+        let addr: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+        require_enabled_transport(&addr, self.enable_uds)?;
+        claim_listen_addr(&self.active_listen_addrs, &addr)?;
+        self.commands
+            .send(Command::Listen(addr))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Starts listening on `addr` and blocks, releasing the GIL, until the concrete bound
+    /// address is known (e.g. the real port libp2p picked for a `tcp/0` request) or
+    /// `timeout_secs` elapses. Essential for ephemeral-port test setups that otherwise have
+    /// to poll `get_events` for `NewListenAddr`.
+    fn listen_blocking(&self, py: Python<'_>, addr: String, timeout_secs: f64) -> PyResult<Vec<String>> {
+        let addr: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+        require_enabled_transport(&addr, self.enable_uds)?;
+        claim_listen_addr(&self.active_listen_addrs, &addr)?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::ListenBlocking(addr, Duration::from_secs_f64(timeout_secs.max(0.0)), reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        py.detach(|| reply_rx.blocking_recv())
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map(|addrs| addrs.iter().map(|a| a.to_string()).collect())
+            .map_err(|e| P2pError::Other(e).into())
+    }
+
+    /// Subscribes to `topic`. Returns `false` if already subscribed.
+    fn subscribe_gossip(&self, topic: String) -> PyResult<bool> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::SubscribeGossip(topic, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(|e| P2pError::Other(e).into())
+    }
+
+    /// Unsubscribes from `topic`. Returns `false` if not currently subscribed.
+    fn unsubscribe_gossip(&self, topic: String) -> PyResult<bool> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::UnsubscribeGossip(topic, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Subscribes to each of `topics` in one call, so apps following many topics (e.g. one
+    /// per shard) don't end up partially subscribed if a later call in a loop were to fail.
+    /// Returns one `(topic, subscribed)` pair per input topic; `subscribed` is `false` if
+    /// that topic was already subscribed or could not be subscribed to.
+    fn subscribe_topics(&self, topics: Vec<String>) -> PyResult<Vec<(String, bool)>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::SubscribeTopics(topics, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Unsubscribes from each of `topics` in one call. Returns one `(topic, was_subscribed)`
+    /// pair per input topic.
+    fn unsubscribe_topics(&self, topics: Vec<String>) -> PyResult<Vec<(String, bool)>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::UnsubscribeTopics(topics, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// The set of topics this node is currently subscribed to.
+    fn get_subscriptions(&self) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetSubscriptions(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// The set of topics a caller has asked to be subscribed to via `subscribe_gossip`/
+    /// `subscribe_topics` and never explicitly unsubscribed from, regardless of whether the
+    /// subscription attempt actually succeeded. The driver thread retries any of these missing
+    /// from `get_subscriptions` roughly every 5 seconds, so a subscribe attempted before this
+    /// node had any mesh peers (or any other transient failure) is eventually retried instead of
+    /// silently staying unsubscribed. This crate keeps a node's `Swarm` alive for its whole
+    /// process lifetime rather than ever rebuilding it, so there's no "swarm restarted, resend
+    /// subscriptions" event to hook here; callers that want subscriptions to survive a full
+    /// process restart should persist this list themselves and pass it to `subscribe_topics` on
+    /// the next `Node`.
+    fn get_desired_subscriptions(&self) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetDesiredSubscriptions(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Publishes `data` on `topic`. If nobody in the mesh is subscribed to `topic`, raises
+    /// with the distinct, catchable message `"InsufficientPeers"` instead of a generic
+    /// error, so callers can tell "silently dropped" apart from an actual failure.
+    fn publish_gossip(&self, topic: String, data: Vec<u8>) -> PyResult<()> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::PublishGossip(topic, data, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(|e| P2pError::Other(e).into())
+    }
+
+    /// Like `publish_gossip`, but `id` is used directly as the gossipsub `MessageId` instead
+    /// of the default hash of source peer id + sequence number. This makes deduplication
+    /// deterministic across restarts (a fresh libp2p identity/sequence counter no longer
+    /// makes a re-published message look new) and across nodes that happen to republish the
+    /// same logical payload, as long as they agree on what `id` to use — e.g. a block's hash.
+    /// Not compatible with `set_gossip_signing_key`: this path does not apply the
+    /// application-level signing envelope. Also exempt from `set_replay_window`: messages
+    /// published this way are never wrapped with a replay envelope, and a receiver with a
+    /// replay window configured recognizes that (see the `EXPLICIT_ID_MARKER` check in
+    /// `run_swarm`'s gossip message handler) and accepts them unconditionally rather than
+    /// rejecting them for lacking one, since gossipsub's own de-dup on `id` already covers
+    /// what the replay window would otherwise be checking for.
+    fn publish_gossip_with_id(&self, topic: String, data: Vec<u8>, id: Vec<u8>) -> PyResult<()> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::PublishGossipWithId(topic, data, id, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(|e| P2pError::Other(e).into())
+    }
+
+    /// Publishes the same `data` to each of `topics`, reusing one encoded (and, if a signing
+    /// key is set, app-signed) payload. Returns `(topic, message_id)` pairs in the same order
+    /// as `topics`; `message_id` is `None` wherever that topic's publish failed (e.g.
+    /// `InsufficientPeers`), with every other topic still attempted rather than the whole
+    /// batch aborting on the first failure.
+    fn publish_to_topics(&self, topics: Vec<String>, data: Vec<u8>) -> PyResult<Vec<(String, Option<String>)>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::PublishToTopics(topics, data, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Throttles `publish_gossip` calls for `topic` to at most `messages_per_sec`, guarding
+    /// against a buggy publish loop flooding the mesh or tripping a remote peer's gossipsub
+    /// scoring thresholds. When exceeded, `publish_gossip` raises `"PublishRateLimited"`
+    /// unless `block` is set, in which case it waits for the next token instead. Pass
+    /// `messages_per_sec=None` to remove a previously set limit.
+    #[pyo3(signature = (topic, messages_per_sec, block=false))]
+    fn set_publish_rate_limit(&self, topic: String, messages_per_sec: Option<f64>, block: bool) -> PyResult<()> {
+        if let Some(rate) = messages_per_sec {
+            if rate <= 0.0 {
+                return Err(P2pError::Other("messages_per_sec must be positive".to_string()).into());
+            }
+        }
+        self.commands
+            .send(Command::SetPublishRateLimit(topic, messages_per_sec.map(|rate| (rate, block))))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Peer ids known to be subscribed to `topic`, maintained from gossipsub's own
+    /// `Subscribed`/`Unsubscribed` events (and pruned on disconnect) rather than anything
+    /// callers report themselves, so it can't drift from what the protocol actually observed.
+    fn get_topic_subscribers(&self, topic: String) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetTopicSubscribers(topic, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Whether this node currently has any mesh peers for `topic` — i.e. whether a
+    /// `publish_gossip` call right now would actually reach anyone.
+    fn has_mesh_peers(&self, topic: String) -> PyResult<bool> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::HasMeshPeers(topic, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// The full gossipsub peer/topic matrix: every peer gossipsub knows about (not just mesh
+    /// members), each with the topics it's subscribed to, as `(peer_id, topics)` pairs.
+    /// Useful alongside `get_topic_subscribers`/`has_mesh_peers` for diagnosing why a published
+    /// message didn't propagate — e.g. a peer subscribed to the topic but not yet in the mesh.
+    fn get_gossipsub_all_peers(&self) -> PyResult<Vec<(String, Vec<String>)>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetGossipsubAllPeers(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Every peer in this node's gossipsub mesh, across all topics. Like `has_mesh_peers` but
+    /// unfiltered by topic and returning the actual peer ids rather than a boolean.
+    fn get_gossipsub_all_mesh_peers(&self) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetGossipsubAllMeshPeers(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Synthesizes `topic`'s raw mesh-peer count against the configured `mesh_n_low`/
+    /// `mesh_n_high` bounds into an at-a-glance health signal, so a starved mesh (the usual
+    /// cause of poor propagation) is obvious without cross-referencing `has_mesh_peers`'
+    /// bare boolean against the gossipsub defaults by hand.
+    fn get_topic_health(&self, py: Python<'_>, topic: String) -> PyResult<Py<PyAny>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetTopicHealth(topic, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let health = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+        let dict = PyDict::new(py);
+        dict.set_item("mesh_size", health.mesh_size)?;
+        dict.set_item("mesh_n_low", health.mesh_n_low)?;
+        dict.set_item("mesh_n_high", health.mesh_n_high)?;
+        dict.set_item("fanout_size", health.fanout_size)?;
+        dict.set_item("status", health.status)?;
+        Ok(dict.into())
+    }
+
+    /// Drops inbound connections from any source IP exceeding `per_ip_per_sec` sustained
+    /// attempts with a burst allowance of `burst`. A connection over the limit is closed as
+    /// soon as it reaches `ConnectionEstablished` — `Swarm::close_connection` only has an
+    /// established connection to close, so this can't happen any earlier at `IncomingConnection`
+    /// time — meaning it never gets to exchange any protocol traffic, but it does complete its
+    /// transport handshake first. Each dropped attempt is reported as a `RateLimited` event
+    /// carrying the offending address.
+    fn set_inbound_rate_limit(&self, per_ip_per_sec: f64, burst: f64) -> PyResult<()> {
+        if per_ip_per_sec <= 0.0 {
+            return Err(P2pError::Other("per_ip_per_sec must be positive".to_string()).into());
+        }
+        if burst <= 0.0 {
+            return Err(P2pError::Other("burst must be positive".to_string()).into());
+        }
+        self.commands
+            .send(Command::SetInboundRateLimit(Some((per_ip_per_sec, burst))))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Designates peers that should be automatically re-dialed (with capped exponential
+    /// backoff) whenever their connection drops. `peers` is a list of
+    /// `(peer_id, multiaddr)` string pairs; calling this again replaces the previous set.
+    fn set_persistent_peers(&self, peers: Vec<(String, String)>) -> PyResult<()> {
+        let mut parsed = HashMap::with_capacity(peers.len());
+        for (peer_id, addr) in peers {
+            let peer_id: PeerId = peer_id
+                .parse()
+                .map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+            let addr: Multiaddr = addr
+                .parse()
+                .map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+            parsed.insert(peer_id, addr);
+        }
+        self.commands
+            .send(Command::SetPersistentPeers(parsed))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Sets the Kademlia mode: `"client"` to only query the DHT, `"server"` to also
+    /// store records and advertise as routable, or `None`/omitted to let libp2p decide
+    /// automatically based on observed reachability.
+    #[pyo3(signature = (mode=None))]
+    fn set_kademlia_mode(&self, mode: Option<&str>) -> PyResult<()> {
+        let mode = match mode {
+            None => None,
+            Some("client") => Some(libp2p::kad::Mode::Client),
+            Some("server") => Some(libp2p::kad::Mode::Server),
+            Some(other) => return Err(P2pError::Other(format!("unknown kademlia mode: {other}")).into()),
+        };
+        self.commands
+            .send(Command::SetKademliaMode(mode))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Adds every `(peer_id, multiaddr)` pair to the Kademlia routing table and queues a
+    /// single bootstrap query, in one call. Returns once the query has been queued, not
+    /// once it completes.
+    fn bootstrap_with(&self, peers: Vec<(String, String)>) -> PyResult<()> {
+        let mut parsed = Vec::with_capacity(peers.len());
+        for (peer_id, addr) in peers {
+            let peer_id: PeerId =
+                peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+            let addr: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+            parsed.push((peer_id, addr));
+        }
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::BootstrapWith(parsed, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx
+            .blocking_recv()
+            .map_err(|e| P2pError::Other(e.to_string()))?
+            .map_err(|e| P2pError::Other(e).into())
+    }
+
+    /// Registers a callback invoked for every inbound `PutRecord` request, as
+    /// `callback(key: bytes, value: bytes) -> bool`. Records are only stored if the callback
+    /// returns `True`; letting this subsystem reject records outside its own namespace/schema
+    /// instead of silently sharing a keyspace with every other record put through Kademlia.
+    /// Pass `None` to remove a previously set validator and accept all records again.
+    #[pyo3(signature = (callback=None))]
+    fn set_record_validator(&self, callback: Option<Py<PyAny>>) -> PyResult<()> {
+        self.commands
+            .send(Command::SetRecordValidator(callback))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Sets (or clears, with `None`) the keypair used to sign published gossipsub payloads at
+    /// the application layer, on top of gossipsub's own transport-level message signing. Every
+    /// published message is re-signed with whichever key is current, so rotating keys takes
+    /// effect on the next `publish_gossip` call; peers recover the signer's public key from the
+    /// message envelope itself and can check it against `GossipMessage.signer_pubkey`.
+    #[pyo3(signature = (keypair=None))]
+    fn set_gossip_signing_key(&self, keypair: Option<&KeypairManager>) -> PyResult<()> {
+        self.commands
+            .send(Command::SetGossipSigningKey(keypair.map(|k| k.keypair.clone())))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Sets (or clears, with `None`) the keypair used to sign outbound `send_file` requests at
+    /// the rr protocol framing level, the same envelope scheme `set_gossip_signing_key` uses for
+    /// gossip. On its own this only adds a verifiable signature for receivers that care to check
+    /// it; pair with `set_require_rr_signature(True)` on the receiving side to actually reject
+    /// unsigned or invalid requests, for security-sensitive RPCs like block submission.
+    #[pyo3(signature = (keypair=None))]
+    fn set_rr_signing_key(&self, keypair: Option<&KeypairManager>) -> PyResult<()> {
+        self.commands
+            .send(Command::SetRrSigningKey(keypair.map(|k| k.keypair.clone())))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// When `required` is `true`, inbound rr requests that aren't signed (or whose signature
+    /// doesn't verify) are rejected outright: no ack is sent back, so the sender's own
+    /// timeout/retry handling (see `OutboundFailure`) takes over, and an
+    /// `UnauthenticatedRequest` event is emitted here instead of processing the request.
+    /// Defaults to `false`, matching the previous behaviour of accepting any request.
+    fn set_require_rr_signature(&self, required: bool) -> PyResult<()> {
+        self.commands
+            .send(Command::SetRequireRrSignature(required))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Declares `addr` as externally reachable immediately, bypassing AutoNAT. The
+    /// address is then advertised via identify and used in Kademlia records.
+    fn add_external_address(&self, addr: String) -> PyResult<()> {
+        let addr: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+        self.commands
+            .send(Command::AddExternalAddress(addr))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Retracts a previously declared external address.
+    fn remove_external_address(&self, addr: String) -> PyResult<()> {
+        let addr: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+        self.commands
+            .send(Command::RemoveExternalAddress(addr))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Returns the addresses this node currently considers externally reachable,
+    /// whether confirmed by AutoNAT or declared via `add_external_address`.
+    fn get_external_addresses(&self) -> PyResult<Vec<String>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetExternalAddresses(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// When `enabled`, `get_dialable_addresses`/`get_signed_peer_record`
+    /// stop reporting a wildcard bind (`/ip4/0.0.0.0/...` or `/ip6/::/...`) verbatim and
+    /// instead expand it into one concrete address per local, non-loopback network interface
+    /// (same port, same remaining components). Peers can never dial the wildcard address
+    /// itself, so leaving this off (the default) means those methods advertise something
+    /// useless whenever the node was told to listen on it. Off by default so a node that binds
+    /// to a specific interface on purpose doesn't unexpectedly start advertising others too.
+    fn enable_interface_expansion(&self, enabled: bool) -> PyResult<()> {
+        self.commands.send(Command::SetInterfaceExpansion(enabled)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Restricts which listen/external addresses `get_external_addresses` (and everything
+    /// built on them, e.g. `get_dialable_addresses`/`get_signed_peer_record`) hand back, and
+    /// which ones this node actually advertises on the wire via identify (and, transitively,
+    /// what peers store for it in their own Kademlia tables — see `AddressFilterMode`'s doc
+    /// comment): `"all"` (the default, no filtering), `"no_loopback"` (drop `127.0.0.1`/`::1`),
+    /// or `"public_only"` (also drop private/link-local/unspecified addresses). A public node
+    /// should generally run with `"public_only"` so it never advertises LAN topology or
+    /// unreachable addresses to remote peers, since this is currently all-or-nothing.
+    /// `add_external_address` bypasses this filter for what identify advertises, same as it
+    /// bypasses AutoNAT: it's an explicit declaration, not a discovered address. What
+    /// `get_external_addresses` reports is still filtered, declared addresses included.
+    fn set_address_filter(&self, mode: &str) -> PyResult<()> {
+        let mode = AddressFilterMode::parse(mode)?;
+        self.commands.send(Command::SetAddressFilter(mode)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Sets a semver requirement (e.g. `">=1.2.0"`) that a peer's identify `agent_version`
+    /// must satisfy to stay connected; useful for shedding outdated peers during a
+    /// coordinated upgrade. A peer is only checked once its `agent_version` parses as
+    /// `<name>/<semver>` — anything else is let through, since there's no version to
+    /// compare. `None` clears the requirement.
+    #[pyo3(signature = (pattern=None))]
+    fn set_min_agent_version(&self, pattern: Option<&str>) -> PyResult<()> {
+        let req = pattern
+            .map(semver::VersionReq::parse)
+            .transpose()
+            .map_err(|e| P2pError::Other(format!("invalid semver requirement: {e}")))?;
+        self.commands.send(Command::SetMinAgentVersion(req)).map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Enables replay-protected gossip: `publish_gossip`/`publish_to_topics` wrap their payload
+    /// with a timestamp and a random nonce, and this node rejects (via
+    /// `report_message_validation_result(Reject)`) any received gossip message whose envelope
+    /// is missing, whose timestamp falls outside `secs` of now, or whose nonce it's already
+    /// seen — including from peers not running replay protection at all, since there's nothing
+    /// to check freshness against. `None` disables it, which is the default: messages are
+    /// delivered as soon as they arrive, exactly as before this feature existed.
+    /// `publish_gossip_with_id` is unaffected, since its explicit-id envelope serves a
+    /// different purpose (message deduplication, not authentication).
+    #[pyo3(signature = (secs=None))]
+    fn set_replay_window(&self, secs: Option<f64>) -> PyResult<()> {
+        self.commands
+            .send(Command::SetReplayWindow(secs.map(Duration::from_secs_f64)))
+            .map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Listen and external addresses, each with `/p2p/<local_peer_id>` appended, ready to
+    /// hand to another node's `dial` as a fully qualified, peer-id-checked multiaddr.
+    fn get_dialable_addresses(&self) -> PyResult<Vec<String>> {
+        let (listen_tx, listen_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetListenAddrs(listen_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let listen_addrs = listen_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+
+        let (external_tx, external_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetExternalAddresses(external_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let external_addrs = external_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+
+        let mut seen = std::collections::HashSet::new();
+        let mut dialable = Vec::new();
+        for addr in listen_addrs.into_iter().map(|a| a.to_string()).chain(external_addrs) {
+            if seen.insert(addr.clone()) {
+                dialable.push(format!("{addr}/p2p/{}", self.local_peer_id));
+            }
+        }
+        Ok(dialable)
+    }
+
+    /// Wires two already-constructed nodes together and blocks until they're connected, for
+    /// test and example code that would otherwise hand-roll "listen on an ephemeral port, poll
+    /// for the bound address, then dial" every time it needs two peers talking to each other.
+    ///
+    /// `node_a` is given a listener if it doesn't already have one (`/ip4/127.0.0.1/tcp/0`,
+    /// i.e. an OS-assigned loopback port), then `node_b` dials it. This uses real TCP loopback
+    /// rather than a dedicated in-memory transport: this crate's transport stack is a single
+    /// combined transport shared by every `Node` (see `ENABLED_TRANSPORTS`), so adding a new
+    /// transport kind would affect every node in the process, not just test helpers. Loopback TCP gives the same
+    /// "no real network, connects almost instantly" property callers actually want here.
+    ///
+    /// Returns once both sides report the connection, or raises on timeout.
+    #[staticmethod]
+    fn connect_nodes(py: Python<'_>, node_a: &Node, node_b: &Node, timeout_secs: f64) -> PyResult<()> {
+        let mut dialable = node_a.get_dialable_addresses()?;
+        if dialable.is_empty() {
+            node_a.listen_blocking(py, "/ip4/127.0.0.1/tcp/0".to_string(), timeout_secs)?;
+            dialable = node_a.get_dialable_addresses()?;
+        }
+        let addr = dialable.into_iter().next().ok_or_else(|| {
+            P2pError::Other("node_a has no dialable address even after listening".to_string())
+        })?;
+
+        node_b.dial(addr)?;
+        if !node_b.wait_for_peers(py, 1, timeout_secs) {
+            return Err(P2pError::Other(format!(
+                "node_b did not connect to node_a within {timeout_secs}s"
+            ))
+            .into());
+        }
+        if !node_a.wait_for_peers(py, 1, timeout_secs) {
+            return Err(P2pError::Other(format!(
+                "node_a did not report the connection from node_b within {timeout_secs}s"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Immediately probes `candidate` (or, if omitted, this node's first known listen
+    /// address) for external reachability, asking a connected/known AutoNAT server to dial it
+    /// back, rather than waiting for the behaviour's own probe schedule. Results arrive as
+    /// `AutonatProbe` events with `direction="outbound"`.
+    #[pyo3(signature = (candidate=None))]
+    fn trigger_autonat_probe(&self, candidate: Option<String>) -> PyResult<()> {
+        let addr: Multiaddr = match candidate {
+            Some(addr) => addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?,
+            None => {
+                let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+                self.commands
+                    .send(Command::GetListenAddrs(reply_tx))
+                    .map_err(|e| P2pError::Other(e.to_string()))?;
+                let addrs = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+                addrs.into_iter().next().ok_or_else(|| {
+                    P2pError::Other("no candidate address given and no listen address known".to_string())
+                })?
+            }
+        };
+        self.commands
+            .send(Command::TriggerAutonatProbe(addr))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(())
+    }
+
+    /// How many consecutive probes have confirmed the behaviour's current assumed NAT status
+    /// (public/private/unknown), up to its configured `confidence_max`. A node with one
+    /// confirming probe shouldn't be treated the same as one with five.
+    fn get_reachability_confidence(&self) -> PyResult<usize> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetReachabilityConfidence(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Whether `addr` was found reachable the last time it was probed. Any successful probe,
+    /// background or via `trigger_autonat_probe`, records `True`; a failure records `False`
+    /// only for probes started by `trigger_autonat_probe`. `None` if no outcome is recorded.
+    fn is_address_reachable(&self, addr: String) -> PyResult<Option<bool>> {
+        let addr: Multiaddr = addr.parse().map_err(|e| P2pError::InvalidMultiaddr(format!("{e}")))?;
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::IsAddressReachable(addr, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()).into())
+    }
+
+    /// Builds a protobuf-encoded, signed `PeerRecord` envelope for this node's current
+    /// listen addresses, suitable for gossiping so peers can verify they weren't
+    /// spoofed.
+    fn get_signed_peer_record(&self) -> PyResult<Vec<u8>> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::GetListenAddrs(reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        let addrs = reply_rx.blocking_recv().map_err(|e| P2pError::Other(e.to_string()))?;
+        let record = libp2p::core::PeerRecord::new(&self.keypair, addrs)
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(record.into_signed_envelope().into_protobuf_encoding())
+    }
+
+    /// Verifies a protobuf-encoded signed peer record produced by `get_signed_peer_record`
+    /// (or received via identify/gossip), checking the embedded signature against the
+    /// embedded public key. Returns `None` if the bytes are malformed or the signature
+    /// doesn't check out, rather than raising, since a bad record from an untrusted peer is
+    /// an expected outcome, not an error.
+    #[staticmethod]
+    fn verify_peer_record(envelope_bytes: Vec<u8>) -> Option<(String, Vec<String>)> {
+        let envelope = libp2p::core::SignedEnvelope::from_protobuf_encoding(&envelope_bytes).ok()?;
+        let record = libp2p::core::PeerRecord::from_signed_envelope(envelope).ok()?;
+        Some((record.peer_id().to_string(), record.addresses().iter().map(|a| a.to_string()).collect()))
+    }
+
+    /// Stops the listener bound to `address`, if one exists. Returns `false` if no
+    /// listener matches, e.g. it was already closed or never existed.
+    fn remove_listener(&self, address: String) -> PyResult<bool> {
+        let listener_id = match self.listeners.lock().unwrap().remove(&address) {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        self.commands
+            .send(Command::RemoveListener(listener_id, reply_tx))
+            .map_err(|e| P2pError::Other(e.to_string()))?;
+        Ok(reply_rx.blocking_recv().unwrap_or(false))
+    }
+
+    /// Per-connection detail snapshot: peer id, remote multiaddr, `"inbound"`/`"outbound"`
+    /// direction, the libp2p connection id, whether it's relayed (see `is_relayed`), and how
+    /// long the connection has been open, in seconds. Useful for debugging mesh health and
+    /// relay usage at a finer grain than `get_external_addresses`.
+    fn get_connection_details(&self, py: Python<'_>) -> PyResult<Vec<Py<PyAny>>> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(connection_id, record)| {
+                let dict = PyDict::new(py);
+                dict.set_item("peer_id", record.peer_id.to_string())?;
+                dict.set_item("remote_addr", &record.remote_addr)?;
+                dict.set_item("direction", record.direction)?;
+                dict.set_item("connection_id", format!("{connection_id:?}"))?;
+                dict.set_item("duration_secs", record.opened_at.elapsed().as_secs_f64())?;
+                dict.set_item("relayed", record.relayed)?;
+                Ok(dict.into())
+            })
+            .collect()
+    }
+
+    /// Whether any open connection to `peer_id` goes through a `/p2p-circuit` relay hop
+    /// rather than reaching it directly, determined by inspecting the connected endpoint's
+    /// multiaddr. With multiple connections to the same peer, a single direct one is enough
+    /// to report `false` — DCUtR hole-punching opens a fresh direct connection alongside the
+    /// relayed one rather than upgrading it in place, so operators care whether *any* direct
+    /// path exists.
+    fn is_relayed(&self, peer_id: String) -> PyResult<bool> {
+        let peer_id: PeerId = peer_id.parse().map_err(|e| P2pError::Other(format!("invalid peer id {peer_id}: {e}")))?;
+        let connections = self.connections.lock().unwrap();
+        let mut relevant = connections.values().filter(|record| record.peer_id == peer_id).peekable();
+        if relevant.peek().is_none() {
+            return Err(P2pError::Other(format!("no open connection to {peer_id}")).into());
+        }
+        Ok(relevant.all(|record| record.relayed))
+    }
+
+    /// Pops and returns the oldest queued event, or `None` if the queue is empty.
+    fn poll_event(&self, py: Python<'_>) -> PyResult<Option<Py<PyAny>>> {
+        let event = self.events.lock().unwrap().pop_front();
+        match event {
+            Some(event) => Ok(Some(event.into_py(py)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Drains and returns all currently queued events, oldest first.
+    fn get_events(&self, py: Python<'_>) -> PyResult<Vec<Py<PyAny>>> {
+        let drained = self.events.lock().unwrap().drain_all();
+        drained.into_iter().map(|e| e.into_py(py)).collect()
+    }
+
+    /// Returns all currently retained events, oldest first, without removing them from
+    /// `poll_event`/`get_events`'s queue. Lets a monitoring tool observe traffic without
+    /// stealing events the main consumer still needs to see. Retention is bounded (see
+    /// `events_since`), so a peeker that never catches up will miss the oldest entries.
+    fn peek_events(&self, py: Python<'_>) -> PyResult<Vec<Py<PyAny>>> {
+        let events = self.events.lock().unwrap().peek_all();
+        events.into_iter().map(|e| e.into_py(py)).collect()
+    }
+
+    /// Returns every event logged after `cursor` (use `0` to start from the beginning) along
+    /// with a new cursor to pass on the next call, as `(events, new_cursor)`. Like
+    /// `peek_events`, this never removes anything from `poll_event`/`get_events`'s queue, and
+    /// repeated calls with the same `cursor` are idempotent. Retention is capped, so a cursor
+    /// that has fallen too far behind silently jumps forward to the oldest event still kept
+    /// rather than erroring.
+    fn events_since(&self, py: Python<'_>, cursor: u64) -> PyResult<(Vec<Py<PyAny>>, u64)> {
+        let (events, new_cursor) = self.events.lock().unwrap().since(cursor);
+        let events = events.into_iter().map(|e| e.into_py(py)).collect::<PyResult<Vec<_>>>()?;
+        Ok((events, new_cursor))
+    }
+
+    /// Blocks for up to `timeout_ms` for at least one event to be queued, then drains and
+    /// returns everything queued so far (possibly empty, if the timeout elapses first).
+    /// Releases the GIL while waiting, like `wait_for_peers`.
+    ///
+    /// Note this crate always drives the swarm on its own background thread and tokio
+    /// runtime (there is no `start_event_loop`/no-hidden-runtime mode) — `poll_once` is a
+    /// bounded-wait convenience over that existing thread's event queue for embedders that
+    /// want to block briefly instead of `poll_event`'s immediate "`None` if empty".
+    fn poll_once(&self, py: Python<'_>, timeout_ms: u64) -> PyResult<Vec<Py<PyAny>>> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        py.detach(|| loop {
+            {
+                let mut events = self.events.lock().unwrap();
+                if !events.is_empty() {
+                    return events.drain_all();
+                }
+            }
+            if Instant::now() >= deadline {
+                return Vec::new();
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        })
+        .into_iter()
+        .map(|e| e.into_py(py))
+        .collect()
+    }
+
+    /// Stops the background swarm-driving thread and its tokio runtime, and blocks until it
+    /// has fully exited. Idempotent: calling this more than once, or letting the node be
+    /// garbage-collected afterwards, is a no-op the second time. Without an explicit `close()`
+    /// or a final `Drop`, the driver thread would never exit on its own — it holds its own
+    /// clone of the command sender (for scheduling delayed redials), so the channel never
+    /// closes just because `Node` itself was dropped.
+    fn close(&self, py: Python<'_>) -> PyResult<()> {
+        let _ = self.commands.send(Command::Shutdown);
+        let handle = self.swarm_thread.lock().unwrap().take();
+        if let Some(handle) = handle {
+            py.detach(|| handle.join()).map_err(|_| P2pError::Other("swarm thread panicked".to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Shutdown);
+        if let Some(handle) = self.swarm_thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod replay_window_tests {
+    use super::*;
+
+    fn current_unix_secs() -> u64 {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+
+    #[test]
+    fn accepts_fresh_enveloped_message_within_window() {
+        let mut seen = HashMap::new();
+        let raw = encode_replay_envelope(b"hello".to_vec());
+        let (data, accept, duplicate) =
+            classify_gossip_message(raw, false, Some(Duration::from_secs(30)), current_unix_secs(), &mut seen);
+        assert!(accept);
+        assert!(!duplicate);
+        assert_eq!(data, b"hello");
+    }
+
+    #[test]
+    fn rejects_replayed_nonce() {
+        let mut seen = HashMap::new();
+        let raw = encode_replay_envelope(b"hello".to_vec());
+        let now = current_unix_secs();
+        let (_, first_accept, _) = classify_gossip_message(raw.clone(), false, Some(Duration::from_secs(30)), now, &mut seen);
+        assert!(first_accept);
+        let (_, second_accept, duplicate) = classify_gossip_message(raw, false, Some(Duration::from_secs(30)), now, &mut seen);
+        assert!(!second_accept);
+        assert!(duplicate);
+    }
+
+    #[test]
+    fn rejects_message_with_no_envelope_when_window_enabled() {
+        let mut seen = HashMap::new();
+        let (_, accept, duplicate) = classify_gossip_message(b"unwrapped".to_vec(), false, Some(Duration::from_secs(30)), 1_000, &mut seen);
+        assert!(!accept);
+        assert!(!duplicate);
+    }
+
+    #[test]
+    fn accepts_unwrapped_message_when_window_disabled() {
+        let mut seen = HashMap::new();
+        let (data, accept, duplicate) = classify_gossip_message(b"unwrapped".to_vec(), false, None, 1_000, &mut seen);
+        assert!(accept);
+        assert!(!duplicate);
+        assert_eq!(data, b"unwrapped");
+    }
+
+    #[test]
+    fn explicit_id_messages_bypass_replay_check_entirely() {
+        let mut seen = HashMap::new();
+        // No replay envelope at all, which would normally be rejected with a window enabled.
+        let (data, accept, duplicate) = classify_gossip_message(b"block-payload".to_vec(), true, Some(Duration::from_secs(30)), 1_000, &mut seen);
+        assert!(accept);
+        assert!(!duplicate);
+        assert_eq!(data, b"block-payload");
+    }
+}
+
+#[cfg(test)]
+mod mdns_prune_tests {
+    use super::*;
+
+    fn addr(port: u16) -> Multiaddr {
+        format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap()
+    }
+
+    #[test]
+    fn only_removes_mdns_contributed_addresses() {
+        let peer_id = PeerId::random();
+        let mdns_addr = addr(1);
+        let identify_addr = addr(2);
+
+        let mut mdns_last_seen = HashMap::from([(peer_id, Instant::now() - Duration::from_secs(60))]);
+        let mut address_book = HashMap::from([(
+            peer_id,
+            std::collections::HashSet::from([mdns_addr.clone(), identify_addr.clone()]),
+        )]);
+        let mut mdns_addresses = HashMap::from([(peer_id, std::collections::HashSet::from([mdns_addr]))]);
+        let mut discovery_methods =
+            HashMap::from([(peer_id, std::collections::HashSet::from(["mdns", "identify"]))]);
+
+        let pruned = prune_expired_mdns_peers(
+            &mut mdns_last_seen,
+            &mut address_book,
+            &mut mdns_addresses,
+            &mut discovery_methods,
+            Duration::from_secs(30),
+        );
+
+        assert_eq!(pruned, 1);
+        assert!(!mdns_last_seen.contains_key(&peer_id));
+        assert_eq!(address_book.get(&peer_id).unwrap(), &std::collections::HashSet::from([identify_addr]));
+        assert_eq!(discovery_methods.get(&peer_id).unwrap(), &std::collections::HashSet::from(["identify"]));
+    }
+
+    #[test]
+    fn drops_peer_entirely_once_no_addresses_or_methods_remain() {
+        let peer_id = PeerId::random();
+        let mdns_addr = addr(1);
+
+        let mut mdns_last_seen = HashMap::from([(peer_id, Instant::now() - Duration::from_secs(60))]);
+        let mut address_book = HashMap::from([(peer_id, std::collections::HashSet::from([mdns_addr.clone()]))]);
+        let mut mdns_addresses = HashMap::from([(peer_id, std::collections::HashSet::from([mdns_addr]))]);
+        let mut discovery_methods = HashMap::from([(peer_id, std::collections::HashSet::from(["mdns"]))]);
+
+        prune_expired_mdns_peers(
+            &mut mdns_last_seen,
+            &mut address_book,
+            &mut mdns_addresses,
+            &mut discovery_methods,
+            Duration::from_secs(30),
+        );
+
+        assert!(!address_book.contains_key(&peer_id));
+        assert!(!discovery_methods.contains_key(&peer_id));
+    }
+
+    #[test]
+    fn leaves_peers_within_ttl_untouched() {
+        let peer_id = PeerId::random();
+        let mut mdns_last_seen = HashMap::from([(peer_id, Instant::now())]);
+        let mut address_book: HashMap<PeerId, std::collections::HashSet<Multiaddr>> =
+            HashMap::from([(peer_id, std::collections::HashSet::from([addr(1)]))]);
+        let mut mdns_addresses = address_book.clone();
+        let mut discovery_methods = HashMap::from([(peer_id, std::collections::HashSet::from(["mdns"]))]);
+
+        let pruned = prune_expired_mdns_peers(
+            &mut mdns_last_seen,
+            &mut address_book,
+            &mut mdns_addresses,
+            &mut discovery_methods,
+            Duration::from_secs(30),
+        );
+
+        assert_eq!(pruned, 0);
+        assert!(address_book.contains_key(&peer_id));
+    }
+}
+
+#[cfg(test)]
+mod peer_health_score_tests {
+    use super::*;
+
+    #[test]
+    fn no_stats_yet_scores_as_neutral_latency_and_perfect_disconnect_history() {
+        let score = peer_health_score(None, 1);
+        // latency_score = 0.5, connection_score = 0.5, disconnect_score = 1.0
+        assert!((score - (0.5 * 0.5 + 0.25 * 0.5 + 0.25 * 1.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lower_latency_scores_higher_than_higher_latency() {
+        let fast = PeerStats { last_ping_rtt: Some(Duration::from_millis(10)), disconnect_count: 0 };
+        let slow = PeerStats { last_ping_rtt: Some(Duration::from_millis(1500)), disconnect_count: 0 };
+        assert!(peer_health_score(Some(&fast), 2) > peer_health_score(Some(&slow), 2));
+    }
+
+    #[test]
+    fn more_disconnects_scores_lower() {
+        let stable = PeerStats { last_ping_rtt: None, disconnect_count: 0 };
+        let flaky = PeerStats { last_ping_rtt: None, disconnect_count: 10 };
+        assert!(peer_health_score(Some(&stable), 1) > peer_health_score(Some(&flaky), 1));
+    }
+
+    #[test]
+    fn score_is_always_within_unit_range() {
+        let extreme = PeerStats { last_ping_rtt: Some(Duration::from_secs(100)), disconnect_count: u32::MAX };
+        let score = peer_health_score(Some(&extreme), 100);
+        assert!((0.0..=1.0).contains(&score));
+    }
+}
+#[cfg(test)]
+mod topic_scheme_tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_unknown_schemes() {
+        assert!(TopicScheme::parse("murmur3", None).is_err());
+    }
+
+    #[test]
+    fn ident_and_sha256_schemes_hash_the_same_topic_differently() {
+        let ident = TopicScheme::parse("ident", None).unwrap();
+        let sha256 = TopicScheme::parse("sha256", None).unwrap();
+        assert_ne!(ident.hash("blocks"), sha256.hash("blocks"));
+    }
+
+    #[test]
+    fn same_scheme_is_deterministic_across_instances() {
+        let a = TopicScheme::parse("sha256", None).unwrap();
+        let b = TopicScheme::parse("sha256", None).unwrap();
+        assert_eq!(a.hash("blocks"), b.hash("blocks"));
+    }
+}
+
+#[cfg(test)]
+mod topic_namespace_tests {
+    use super::*;
+
+    #[test]
+    fn namespace_changes_the_topic_hash() {
+        let unscoped = TopicScheme::parse("ident", None).unwrap();
+        let scoped = TopicScheme::parse("ident", Some("testnet".to_string())).unwrap();
+        assert_ne!(unscoped.hash("blocks"), scoped.hash("blocks"));
+    }
+
+    #[test]
+    fn different_namespaces_never_collide_on_the_same_topic_name() {
+        let a = TopicScheme::parse("ident", Some("chain-a".to_string())).unwrap();
+        let b = TopicScheme::parse("ident", Some("chain-b".to_string())).unwrap();
+        assert_ne!(a.hash("blocks"), b.hash("blocks"));
+    }
+
+    #[test]
+    fn namespaced_prepends_namespace_with_a_slash() {
+        let scoped = TopicScheme::parse("ident", Some("testnet".to_string())).unwrap();
+        assert_eq!(scoped.namespaced("blocks"), "testnet/blocks");
+        let unscoped = TopicScheme::parse("ident", None).unwrap();
+        assert_eq!(unscoped.namespaced("blocks"), "blocks");
+    }
+}
+
+#[cfg(test)]
+mod dial_queue_tests {
+    use super::*;
+
+    fn build_test_swarm() -> Swarm<Behaviour> {
+        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        let behaviour = behaviour::build(
+            &keypair,
+            behaviour::DEFAULT_KAD_PROTOCOL,
+            behaviour::IDENTIFY_PROTOCOL_VERSION,
+            Duration::from_secs(15),
+            64 * 1024,
+            false,
+            behaviour::GossipTuning::default(),
+            behaviour::KadTuning::default(),
+            kad::store::MemoryStoreConfig::default(),
+            true,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        libp2p::SwarmBuilder::with_existing_identity(keypair)
+            .with_tokio()
+            .with_tcp(Default::default(), libp2p::noise::Config::new, libp2p::yamux::Config::default)
+            .unwrap()
+            .with_behaviour(|_| behaviour)
+            .unwrap()
+            .build()
+    }
+
+    fn addr(port: u16) -> Multiaddr {
+        format!("/ip4/127.0.0.1/tcp/{port}").parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn stops_starting_dials_once_the_limit_is_reached() {
+        let mut swarm = build_test_swarm();
+        let mut dial_started = HashMap::new();
+        let mut pending_dials = HashMap::new();
+        let mut dial_queue: std::collections::VecDeque<QueuedDial> = std::collections::VecDeque::from([
+            QueuedDial::Single(addr(1)),
+            QueuedDial::Single(addr(2)),
+            QueuedDial::Single(addr(3)),
+        ]);
+
+        drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, Some(2));
+
+        assert_eq!(pending_dials.len(), 2);
+        assert_eq!(dial_started.len(), 2);
+        assert_eq!(dial_queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resumes_draining_once_a_slot_frees_up() {
+        let mut swarm = build_test_swarm();
+        let mut dial_started = HashMap::new();
+        let mut pending_dials = HashMap::new();
+        let mut dial_queue: std::collections::VecDeque<QueuedDial> =
+            std::collections::VecDeque::from([QueuedDial::Single(addr(1)), QueuedDial::Single(addr(2))]);
+
+        drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, Some(1));
+        assert_eq!(pending_dials.len(), 1);
+        assert_eq!(dial_queue.len(), 1);
+
+        // Simulates what ConnectionEstablished/OutgoingConnectionError does: freeing the slot.
+        let freed_id = *pending_dials.keys().next().unwrap();
+        pending_dials.remove(&freed_id);
+
+        drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, Some(1));
+        assert_eq!(pending_dials.len(), 1);
+        assert!(dial_queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn drains_the_whole_queue_with_no_limit_set() {
+        let mut swarm = build_test_swarm();
+        let mut dial_started = HashMap::new();
+        let mut pending_dials = HashMap::new();
+        let mut dial_queue: std::collections::VecDeque<QueuedDial> = std::collections::VecDeque::from([
+            QueuedDial::Single(addr(1)),
+            QueuedDial::Single(addr(2)),
+            QueuedDial::Single(addr(3)),
+        ]);
+
+        drain_dial_queue(&mut swarm, &mut dial_started, &mut pending_dials, &mut dial_queue, None);
+
+        assert_eq!(pending_dials.len(), 3);
+        assert!(dial_queue.is_empty());
+    }
+}
+
+
+#[cfg(test)]
+mod inbound_rate_limit_tests {
+    use super::*;
+    use libp2p::ping;
+
+    fn build_test_swarm() -> Swarm<ping::Behaviour> {
+        libp2p::SwarmBuilder::with_new_identity()
+            .with_tokio()
+            .with_tcp(Default::default(), libp2p::noise::Config::new, libp2p::yamux::Config::default)
+            .unwrap()
+            .with_behaviour(|_| ping::Behaviour::default())
+            .unwrap()
+            .build()
+    }
+
+    /// Proves the bug the review flagged: `Swarm::close_connection` only has an effect on a
+    /// connection once it reaches the established pool. Calling it with a connection id from
+    /// `IncomingConnection` (still pending) is a silent no-op; the fix in `run_swarm` relies on
+    /// deferring the close to `ConnectionEstablished`, which this confirms actually tears the
+    /// connection down.
+    #[tokio::test]
+    async fn close_connection_only_takes_effect_after_connection_established() {
+        let mut listener = build_test_swarm();
+        listener.listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+        let listen_addr = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = listener.select_next_some().await {
+                break address;
+            }
+        };
+
+        let mut dialer = build_test_swarm();
+        dialer.dial(listen_addr).unwrap();
+        tokio::spawn(async move {
+            loop {
+                dialer.select_next_some().await;
+            }
+        });
+
+        let pending_connection_id = loop {
+            if let SwarmEvent::IncomingConnection { connection_id, .. } = listener.select_next_some().await {
+                break connection_id;
+            }
+        };
+        listener.close_connection(pending_connection_id);
+
+        let established_connection_id = loop {
+            if let SwarmEvent::ConnectionEstablished { connection_id, .. } = listener.select_next_some().await {
+                break connection_id;
+            }
+        };
+        assert_eq!(established_connection_id, pending_connection_id, "closing while pending must not have torn it down");
+
+        listener.close_connection(established_connection_id);
+        let closed_connection_id = loop {
+            if let SwarmEvent::ConnectionClosed { connection_id, .. } = listener.select_next_some().await {
+                break connection_id;
+            }
+        };
+        assert_eq!(closed_connection_id, established_connection_id);
+    }
+}
+
+#[cfg(test)]
+mod signed_peer_record_tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn verifies_a_genuine_record() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let addrs: Vec<Multiaddr> = vec!["/ip4/127.0.0.1/tcp/4001".parse().unwrap()];
+        let record = libp2p::core::PeerRecord::new(&keypair, addrs.clone()).unwrap();
+        let envelope_bytes = record.into_signed_envelope().into_protobuf_encoding();
+
+        let (verified_peer_id, verified_addrs) = Node::verify_peer_record(envelope_bytes).unwrap();
+        assert_eq!(verified_peer_id, peer_id.to_string());
+        assert_eq!(verified_addrs, vec![addrs[0].to_string()]);
+    }
+
+    #[test]
+    fn rejects_a_tampered_record() {
+        let keypair = Keypair::generate_ed25519();
+        let addrs: Vec<Multiaddr> = vec!["/ip4/127.0.0.1/tcp/4001".parse().unwrap()];
+        let record = libp2p::core::PeerRecord::new(&keypair, addrs).unwrap();
+        let mut envelope_bytes = record.into_signed_envelope().into_protobuf_encoding();
+        *envelope_bytes.last_mut().unwrap() ^= 0xff;
+
+        assert!(Node::verify_peer_record(envelope_bytes).is_none());
+    }
+
+    #[test]
+    fn rejects_garbage_bytes() {
+        assert!(Node::verify_peer_record(b"not a signed envelope".to_vec()).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use pyo3::PyResult;
+use serde::{Deserialize, Serialize};
+
+use crate::error::P2pError;
+
+/// Chunks larger than this are never produced by `send_file`; keeps any single
+/// request-response frame small enough that a slow receiver's ack round-trip, not
+/// unbounded buffering, is what throttles the sender.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// One length-prefixed frame of a file transfer, carried as the request payload of the
+/// `rr` request-response behaviour.
+#[derive(Serialize, Deserialize)]
+pub struct FileChunk {
+    pub transfer_id: u64,
+    pub protocol: String,
+    pub seq: u32,
+    pub total: u32,
+    pub data: Vec<u8>,
+}
+
+/// Acknowledges a single `FileChunk`, sent back as the request-response reply so the
+/// sender knows it's safe to read and send the next chunk.
+#[derive(Serialize, Deserialize)]
+pub struct FileAck {
+    pub transfer_id: u64,
+    pub seq: u32,
+}
+
+pub fn encode_chunk(chunk: &FileChunk) -> Vec<u8> {
+    bincode::serialize(chunk).expect("FileChunk serialization is infallible")
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> PyResult<FileChunk> {
+    bincode::deserialize(bytes).map_err(|e| P2pError::Other(format!("malformed file chunk: {e}")).into())
+}
+
+pub fn encode_ack(ack: &FileAck) -> Vec<u8> {
+    bincode::serialize(ack).expect("FileAck serialization is infallible")
+}
+
+pub fn decode_ack(bytes: &[u8]) -> Option<FileAck> {
+    bincode::deserialize(bytes).ok()
+}
+
+/// A generic RPC request carried over the same `rr` request-response wire protocol as file
+/// chunks, tagged with a caller-chosen logical `protocol` name since this crate registers only
+/// one actual libp2p protocol for `rr` (see `behaviour::RR_PROTOCOL`). Sent by `Node.request`
+/// and surfaced to Python as `NodeEvent::IncomingRequest`; distinguished from a `FileChunk` on
+/// the receiving end purely by which one successfully deserializes, the same way `decode_ack`
+/// is distinguished from `decode_chunk`.
+#[derive(Serialize, Deserialize)]
+pub struct RrRequest {
+    pub protocol: String,
+    pub data: Vec<u8>,
+}
+
+pub fn encode_rr_request(request: &RrRequest) -> Vec<u8> {
+    bincode::serialize(request).expect("RrRequest serialization is infallible")
+}
+
+pub fn decode_rr_request(bytes: &[u8]) -> PyResult<RrRequest> {
+    bincode::deserialize(bytes).map_err(|e| P2pError::Other(format!("malformed rr request: {e}")).into())
+}